@@ -0,0 +1,339 @@
+//! Reading files from APK and app bundles (XAPK, APKS, APKM)
+//!
+//! Bundles are ZIP archives with base APK and split APKs inside
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use zip::ZipArchive;
+
+use crate::{
+    axml,
+    db::{Picture, DB},
+    info::App,
+    media::{is_picture, is_sound, Pictures},
+    profile::AppProfile,
+};
+
+const MANIFEST: &str = "AndroidManifest.xml";
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+type Zip<'a> = ZipArchive<Cursor<Cow<'a, [u8]>>>;
+
+/// Extract words db from APK or app bundle. If db is not found at known path,
+/// files in all APKs are checked. Returns path to found db inside APK, if it
+/// differs from known path
+pub fn extract_db(
+    profile: &dyn AppProfile,
+    apk: &[u8],
+    db_path: impl AsRef<Path>,
+) -> Result<Option<String>> {
+    let db_name = profile.apk_db_path();
+    if let Some(mut apk) = open_apk(apk, |zip| has_entry(zip, db_name))? {
+        std::fs::write(&db_path, read_entry(&mut apk, db_name)?)?;
+        return Ok(None);
+    }
+
+    let name = find_in_apks(apk, |mut zip| scan_db(&mut zip, &db_path))?
+        .ok_or_else(|| anyhow!("words database not found in APK"))?;
+    Ok(Some(name))
+}
+
+/// Search for SQLite file with words database and write it to `db_path`.
+/// Returns path to database inside APK
+fn scan_db(apk: &mut Zip, db_path: impl AsRef<Path>) -> Result<Option<String>> {
+    let db_path = db_path.as_ref();
+    // candidates are checked in separate file to not break cached db
+    let tmp_path = db_path.with_extension("tmp");
+    let mut found = None;
+    for i in 0..apk.len() {
+        let mut file = apk.by_index(i)?;
+        if file.is_dir() || file.size() < SQLITE_HEADER.len() as u64 {
+            continue;
+        }
+
+        let mut header = [0; SQLITE_HEADER.len()];
+        file.read_exact(&mut header)?;
+        if &header != SQLITE_HEADER {
+            continue;
+        }
+
+        let mut buf = header.to_vec();
+        file.read_to_end(&mut buf)?;
+        std::fs::write(&tmp_path, buf)?;
+        if DB::new(&tmp_path).and_then(|db| db.check_schema()).is_ok() {
+            found = Some(file.name().to_string());
+            break;
+        }
+    }
+
+    if found.is_some() {
+        std::fs::rename(&tmp_path, db_path)?;
+    } else if tmp_path.exists() {
+        std::fs::remove_file(&tmp_path)?;
+    }
+    Ok(found)
+}
+
+/// Extract pictures of words from APK or app bundle to `dir`. Files are
+/// matched by name to `pictures` from db, as in [`Pictures::find`], e.g.
+/// `assets/pictures/1234.jpg`. Returns number of extracted pictures
+pub fn extract_pictures(apk: &[u8], dir: impl AsRef<Path>, pictures: &[Picture]) -> Result<usize> {
+    let names: HashSet<_> = pictures.iter().flat_map(Pictures::names).collect();
+    extract_files(apk, dir.as_ref(), |n| {
+        is_picture(n) && !n.starts_with("res/") && file_stem(n).is_some_and(|s| names.contains(s))
+    })
+}
+
+/// Extract audio of words from APK or app bundle to `dir`. Files are matched by
+/// name in lower case to `names`, see
+/// [`Sounds::names`](crate::media::Sounds::names), e.g.
+/// `assets/audio/123.mp3`. Returns number of extracted files
+pub fn extract_sounds(apk: &[u8], dir: impl AsRef<Path>, names: &HashSet<String>) -> Result<usize> {
+    extract_files(apk, dir.as_ref(), |n| {
+        is_sound(n)
+            && !n.starts_with("res/")
+            && file_stem(n).is_some_and(|s| names.contains(&s.to_lowercase()))
+    })
+}
+
+/// Extract files, for which `is_match` is true, to `dir`, without
+/// subdirectories. Files with the same name in different directories are
+/// rejected, as they cannot be told apart
+fn extract_files(apk: &[u8], dir: &Path, is_match: impl Fn(&str) -> bool) -> Result<usize> {
+    let Some(mut apk) = open_apk(apk, |zip| zip.file_names().any(&is_match))? else {
+        return Ok(0);
+    };
+
+    let mut names: HashMap<&str, &str> = HashMap::new();
+    for name in apk.file_names().filter(|n| is_match(n)) {
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        if let Some(other) = names.insert(file_name, name) {
+            return Err(anyhow!(
+                "files {other} and {name} in APK have the same name"
+            ));
+        }
+    }
+    let names: Vec<_> = names.into_values().map(ToOwned::to_owned).collect();
+
+    std::fs::create_dir_all(dir)?;
+    for name in &names {
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        std::fs::write(dir.join(file_name), read_entry(&mut apk, name)?)?;
+    }
+    Ok(names.len())
+}
+
+fn file_stem(name: &str) -> Option<&str> {
+    Path::new(name).file_stem()?.to_str()
+}
+
+/// Detect app by package name from APK's manifest
+pub fn detect_app(apk: &[u8]) -> Result<App> {
+    let mut apk = open_apk(apk, |zip| has_entry(zip, MANIFEST))?
+        .ok_or_else(|| anyhow!("{MANIFEST} not found, file is not an APK"))?;
+    let manifest = read_entry(&mut apk, MANIFEST)?;
+    let package = axml::find_attribute(&manifest, "manifest", "package")?
+        .ok_or_else(|| anyhow!("package name not found in {MANIFEST}"))?;
+    App::from_package(&package).ok_or_else(|| anyhow!("unknown app: {package}"))
+}
+
+/// Open APK file, for which `is_match` is true. If file is a bundle, search for
+/// this APK inside
+fn open_apk(file: &[u8], is_match: impl Fn(&Zip) -> bool) -> Result<Option<Zip<'_>>> {
+    find_in_apks(file, |zip| Ok(is_match(&zip).then_some(zip)))
+}
+
+/// Call `f` for file and, if it is a bundle, for every APK inside, until `f`
+/// returns something
+fn find_in_apks<'a, T>(
+    file: &'a [u8],
+    mut f: impl FnMut(Zip<'a>) -> Result<Option<T>>,
+) -> Result<Option<T>> {
+    let zip = ZipArchive::new(Cursor::new(Cow::Borrowed(file)))?;
+    let mut apks: Vec<_> = zip
+        .file_names()
+        .filter(|n| n.ends_with(".apk"))
+        .map(ToOwned::to_owned)
+        .collect();
+    // split APKs only contain resources for specific configurations, check them last
+    apks.sort_by_key(|n| is_split_apk(n));
+
+    let mut bundle = zip.clone();
+    if let Some(found) = f(zip)? {
+        return Ok(Some(found));
+    }
+    for apk in apks {
+        let nested = ZipArchive::new(Cursor::new(Cow::Owned(read_entry(&mut bundle, &apk)?)))?;
+        if let Some(found) = f(nested)? {
+            return Ok(Some(found));
+        }
+    }
+    Ok(None)
+}
+
+fn is_split_apk(name: &str) -> bool {
+    let name = name.rsplit('/').next().unwrap_or(name);
+    name.starts_with("split_") || name.starts_with("config.")
+}
+
+fn has_entry(zip: &Zip, name: &str) -> bool {
+    zip.file_names().any(|n| n == name)
+}
+
+fn read_entry(zip: &mut Zip, name: &str) -> Result<Vec<u8>> {
+    let mut file = zip.by_name(name)?;
+    let mut buf = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use crate::{axml::tests::manifest, media::Sounds, query::app_profile, test_dir::TestDir};
+
+    use super::*;
+
+    fn zip_of(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        for (name, data) in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_open_apk() {
+        const DB: &str = "res/raw/englishwordsdb";
+        let open = |file: &[u8]| {
+            open_apk(file, |zip| has_entry(zip, DB))
+                .unwrap()
+                .map(|mut zip| read_entry(&mut zip, DB).unwrap())
+        };
+
+        let apk = zip_of(&[(DB, b"db")]);
+        let other_apk = zip_of(&[("res/kv", b"other")]);
+        let split_apk = zip_of(&[("lib/x86/lib.so", b"")]);
+
+        assert_eq!(open(&apk), Some(b"db".to_vec()));
+
+        let bundle = zip_of(&[
+            ("config.ru.apk", &split_apk),
+            ("ru.poas.englishwords.apk", &apk),
+            ("manifest.json", b"{}"),
+        ]);
+        assert_eq!(open(&bundle), Some(b"db".to_vec()));
+
+        let bundle = zip_of(&[("base.apk", &other_apk), ("split_config.apk", &split_apk)]);
+        assert_eq!(open(&bundle), None);
+        assert_eq!(open(&other_apk), None);
+    }
+
+    #[test]
+    fn test_extract_pictures() {
+        let dir = TestDir::new();
+
+        let pictures = [
+            Picture::new(Some("pixabay".into()), Some("1234".into())).unwrap(),
+            Picture::new(Some("pexels".into()), Some("1".into())).unwrap(),
+        ];
+        let apk = zip_of(&[
+            ("assets/pictures/1234.jpg", b"picture"),
+            ("assets/pictures/5678.jpg", b"unknown"),
+            ("assets/icon.png", b"icon"),
+            ("res/drawable/1.png", b"resource"),
+        ]);
+        let bundle = zip_of(&[("base.apk", &apk)]);
+        assert_eq!(extract_pictures(&bundle, &dir, &pictures).unwrap(), 1);
+        assert_eq!(std::fs::read(dir.join("1234.jpg")).unwrap(), b"picture");
+
+        let apk = zip_of(&[
+            ("assets/small/1234.jpg", b"small"),
+            ("assets/large/1234.jpg", b"large"),
+        ]);
+        assert!(extract_pictures(&apk, &dir, &pictures).is_err());
+    }
+
+    #[test]
+    fn test_extract_sounds() {
+        let dir = TestDir::new();
+
+        let names = [(1, "one"), (2, "Apple")]
+            .into_iter()
+            .flat_map(|(id, text)| Sounds::names(id, [text]))
+            .collect();
+        let apk = zip_of(&[
+            ("assets/audio/1.mp3", b"1"),
+            ("assets/audio/apple.ogg", b"apple"),
+            ("assets/audio/correct.mp3", b"ui"),
+            ("res/raw/one.ogg", b"ui"),
+            ("assets/words.txt", b""),
+        ]);
+        assert_eq!(extract_sounds(&apk, &dir, &names).unwrap(), 2);
+        assert_eq!(std::fs::read(dir.join("apple.ogg")).unwrap(), b"apple");
+    }
+
+    #[test]
+    fn test_scan_db() {
+        let dir = TestDir::new();
+        let db_path = dir.join("words.db");
+
+        let source = dir.join("source.db");
+        let conn = rusqlite::Connection::open(&source).unwrap();
+        conn.execute("create table other (id integer)", []).unwrap();
+        let other_db = std::fs::read(&source).unwrap();
+        for t in DB::TABLES {
+            conn.execute(&format!("create table {t} (id integer)"), [])
+                .unwrap();
+        }
+        drop(conn);
+        let words_db = std::fs::read(&source).unwrap();
+
+        let apk = zip_of(&[
+            ("res/raw/other", &other_db),
+            ("res/raw/words", &words_db),
+            ("res/raw/text", b"text"),
+        ]);
+        let mut zip = ZipArchive::new(Cursor::new(Cow::Owned(apk))).unwrap();
+        let found = scan_db(&mut zip, &db_path).unwrap();
+        assert_eq!(found.as_deref(), Some("res/raw/words"));
+        assert_eq!(std::fs::read(&db_path).unwrap(), words_db);
+
+        let apk = zip_of(&[("res/raw/other", &other_db)]);
+        let mut zip = ZipArchive::new(Cursor::new(Cow::Borrowed(apk.as_slice()))).unwrap();
+        assert_eq!(scan_db(&mut zip, &db_path).unwrap(), None);
+        assert!(!db_path.with_extension("tmp").exists());
+
+        // db is in the second APK of bundle
+        let profile = app_profile(App::English).unwrap();
+        let bundle = zip_of(&[
+            ("base.apk", &apk),
+            ("asset_pack.apk", &zip_of(&[("assets/words", &words_db)])),
+        ]);
+        let found = extract_db(profile, &bundle, &db_path).unwrap();
+        assert_eq!(found.as_deref(), Some("assets/words"));
+        assert_eq!(std::fs::read(&db_path).unwrap(), words_db);
+    }
+
+    #[test]
+    fn test_detect_app() {
+        let apk = zip_of(&[(MANIFEST, &manifest("ru.poas.japanesewords"))]);
+        assert_eq!(detect_app(&apk).unwrap(), App::Japanese);
+
+        let bundle = zip_of(&[("base.apk", &apk)]);
+        assert_eq!(detect_app(&bundle).unwrap(), App::Japanese);
+
+        let apk = zip_of(&[(MANIFEST, &manifest("com.example.app"))]);
+        assert!(detect_app(&apk).is_err());
+    }
+}
@@ -1,9 +1,102 @@
+use std::path::PathBuf;
+
 use clap::Parser;
 
+use crate::{
+    info::{App, Language},
+    table::Escape,
+    Format,
+};
+
 /// Convert words lists from Reword apps
+///
+/// Values which are not passed as arguments are asked interactively
 #[derive(Debug, Parser)]
 pub struct Cli {
     /// Do not use cached extracted data. Updates cache with new data
     #[arg(long)]
     pub no_cache: bool,
+
+    /// App to import, by kind (e.g. eng, jap) or language name. Detected
+    /// automatically when APK is given
+    #[arg(long)]
+    pub app: Option<App>,
+
+    /// Path to APK file. Database is extracted even if it is cached
+    #[arg(long)]
+    pub apk: Option<PathBuf>,
+
+    /// Path to already extracted words database. APK is not needed and cache
+    /// is not used
+    #[arg(long, conflicts_with_all = ["apk", "no_cache"])]
+    pub db: Option<PathBuf>,
+
+    /// Translate language, by kind (e.g. rus, eng) or name
+    #[arg(long)]
+    pub lang: Option<Language>,
+
+    /// Script of learned language, for apps which have several
+    #[arg(long)]
+    pub script: Option<Language>,
+
+    /// Directory with pictures for words, added to apkg. Files are matched by
+    /// picture's id: `1234.jpg` or `pixabay_1234.jpg`. By default pictures
+    /// extracted from APK are used, if there are any
+    #[arg(long)]
+    pub pictures: Option<PathBuf>,
+
+    /// Put words into subdeck per category in apkg. Word in several categories
+    /// goes to the category with the fewest words
+    #[arg(long)]
+    pub subdecks: bool,
+
+    /// Prefix of category tags in apkg, may be nested with `::`. Default is
+    /// `reword::<app>`, e.g. `reword::english`, then tag is
+    /// `reword::english::food`
+    #[arg(long)]
+    pub tag_prefix: Option<String>,
+
+    /// Existing Anki collection (.apkg or collection.anki2) to update. Only
+    /// new words and words changed in app are exported, fields edited in Anki
+    /// are kept
+    #[arg(long, value_name = "COLLECTION")]
+    pub update: Option<PathBuf>,
+
+    /// Command for generating audio of words for apkg, e.g. `espeak-ng -v
+    /// {lang} -w {output} {text}`. Placeholders: {text}, {lang} (ISO 639-1
+    /// code) and {output} (path to WAV file). Without {text}, word is written
+    /// to stdin. Generated audio is cached
+    #[arg(long)]
+    pub tts_command: Option<String>,
+
+    /// Path to exported file
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Format of exported file: apkg, csv, tsv, json or ndjson. By default
+    /// detected from output's extension, or apkg
+    #[arg(long)]
+    pub format: Option<Format>,
+
+    /// Delimiter of columns for csv and tsv. Comma for csv and tab for tsv by
+    /// default
+    #[arg(long)]
+    pub delimiter: Option<char>,
+
+    /// Do not write names of columns for csv and tsv
+    #[arg(long)]
+    pub no_header: bool,
+
+    /// How special characters are escaped for csv and tsv: quote (as in RFC
+    /// 4180) or backslash
+    #[arg(long, default_value = "quote")]
+    pub escape: Escape,
+
+    /// Export only words from this category, by id or name. Can be repeated
+    #[arg(long = "category", value_name = "CATEGORY")]
+    pub categories: Vec<String>,
+
+    /// Export words from all categories without asking
+    #[arg(long, conflicts_with = "categories")]
+    pub all_categories: bool,
 }
@@ -0,0 +1,214 @@
+//! Minimal reader of Android binary XML, which is used for AndroidManifest.xml
+//! inside APK
+//!
+//! Only string pool and start elements are parsed, everything else is skipped
+
+use anyhow::{anyhow, Result};
+
+const RES_XML_TYPE: u16 = 0x0003;
+const RES_STRING_POOL_TYPE: u16 = 0x0001;
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+
+const UTF8_FLAG: u32 = 1 << 8;
+const NO_INDEX: u32 = u32::MAX;
+/// Type of attribute's typed value, which means that value is index in string pool
+const TYPE_STRING: u8 = 0x03;
+
+/// Find value of attribute `attr` of first element `element`
+pub fn find_attribute(data: &[u8], element: &str, attr: &str) -> Result<Option<String>> {
+    let r = Reader(data);
+    if r.u16(0)? != RES_XML_TYPE {
+        return Err(anyhow!("not a binary XML"));
+    }
+
+    let mut strings = vec![];
+    let mut pos = r.u16(2)? as usize;
+    while pos < data.len() {
+        let chunk_type = r.u16(pos)?;
+        let header_size = r.u16(pos + 2)? as usize;
+        let size = r.u32(pos + 4)? as usize;
+        if size < 8 {
+            return Err(anyhow!("invalid chunk size at {pos}"));
+        }
+
+        match chunk_type {
+            RES_STRING_POOL_TYPE => strings = read_strings(&r, pos)?,
+            RES_XML_START_ELEMENT_TYPE => {
+                let ext = pos + header_size;
+                if strings.get(r.u32(ext + 4)? as usize) == Some(&element.to_string()) {
+                    let start = r.u16(ext + 8)? as usize;
+                    let attr_size = r.u16(ext + 10)? as usize;
+                    let count = r.u16(ext + 12)? as usize;
+                    for i in 0..count {
+                        let a = ext + start + i * attr_size;
+                        if strings.get(r.u32(a + 4)? as usize) != Some(&attr.to_string()) {
+                            continue;
+                        }
+                        let raw = r.u32(a + 8)?;
+                        let index = if raw != NO_INDEX {
+                            raw
+                        } else if r.u8(a + 15)? == TYPE_STRING {
+                            r.u32(a + 16)?
+                        } else {
+                            return Ok(None);
+                        };
+                        return Ok(strings.get(index as usize).cloned());
+                    }
+                    return Ok(None);
+                }
+            }
+            _ => {}
+        }
+        pos += size;
+    }
+    Ok(None)
+}
+
+fn read_strings(r: &Reader, chunk: usize) -> Result<Vec<String>> {
+    let count = r.u32(chunk + 8)? as usize;
+    let utf8 = r.u32(chunk + 16)? & UTF8_FLAG != 0;
+    let strings_start = chunk + r.u32(chunk + 20)? as usize;
+    let offsets = chunk + r.u16(chunk + 2)? as usize;
+
+    (0..count)
+        .map(|i| {
+            let pos = strings_start + r.u32(offsets + i * 4)? as usize;
+            if utf8 {
+                r.utf8(pos)
+            } else {
+                r.utf16(pos)
+            }
+        })
+        .collect()
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes(&self, pos: usize, len: usize) -> Result<&[u8]> {
+        self.0
+            .get(pos..pos + len)
+            .ok_or_else(|| anyhow!("unexpected end of binary XML"))
+    }
+    fn u8(&self, pos: usize) -> Result<u8> {
+        Ok(self.bytes(pos, 1)?[0])
+    }
+    fn u16(&self, pos: usize) -> Result<u16> {
+        let b = self.bytes(pos, 2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+    fn u32(&self, pos: usize) -> Result<u32> {
+        let b = self.bytes(pos, 4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    /// Length is stored in 1 or 2 bytes, depending on high bit
+    fn utf8_len(&self, pos: usize) -> Result<(usize, usize)> {
+        let first = self.u8(pos)? as usize;
+        if first & 0x80 == 0 {
+            Ok((first, 1))
+        } else {
+            Ok((((first & 0x7f) << 8) | self.u8(pos + 1)? as usize, 2))
+        }
+    }
+    fn utf8(&self, pos: usize) -> Result<String> {
+        // length in utf-16 units, not needed
+        let (_, skip) = self.utf8_len(pos)?;
+        let (len, skip2) = self.utf8_len(pos + skip)?;
+        let b = self.bytes(pos + skip + skip2, len)?;
+        Ok(String::from_utf8_lossy(b).into_owned())
+    }
+    /// Length is stored in 1 or 2 u16, depending on high bit
+    fn utf16(&self, pos: usize) -> Result<String> {
+        let first = self.u16(pos)? as usize;
+        let (len, skip) = if first & 0x8000 == 0 {
+            (first, 2)
+        } else {
+            (((first & 0x7fff) << 16) | self.u16(pos + 2)? as usize, 4)
+        };
+        let units = (0..len)
+            .map(|i| self.u16(pos + skip + i * 2))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(String::from_utf16_lossy(&units))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Build minimal binary XML with single element
+    pub fn manifest(package: &str) -> Vec<u8> {
+        let strings = ["manifest", "versionCode", "package"];
+
+        let mut pool_data = vec![];
+        let mut offsets = vec![];
+        for s in strings.iter().copied().chain([package]) {
+            offsets.push(pool_data.len() as u32);
+            let units: Vec<u16> = s.encode_utf16().collect();
+            pool_data.extend((units.len() as u16).to_le_bytes());
+            pool_data.extend(units.iter().flat_map(|u| u.to_le_bytes()));
+            pool_data.extend([0, 0]);
+        }
+        let pool_header = 28;
+        let strings_start = pool_header + offsets.len() * 4;
+        let mut pool = vec![];
+        pool.extend(RES_STRING_POOL_TYPE.to_le_bytes());
+        pool.extend((pool_header as u16).to_le_bytes());
+        pool.extend(((strings_start + pool_data.len()) as u32).to_le_bytes());
+        pool.extend((offsets.len() as u32).to_le_bytes());
+        pool.extend(0u32.to_le_bytes());
+        pool.extend(0u32.to_le_bytes());
+        pool.extend((strings_start as u32).to_le_bytes());
+        pool.extend(0u32.to_le_bytes());
+        pool.extend(offsets.iter().flat_map(|o| o.to_le_bytes()));
+        pool.extend(pool_data);
+
+        let attr = |name: u32, raw: u32, data_type: u8, data: u32| {
+            let mut a = vec![];
+            a.extend(NO_INDEX.to_le_bytes());
+            a.extend(name.to_le_bytes());
+            a.extend(raw.to_le_bytes());
+            a.extend(8u16.to_le_bytes());
+            a.push(0);
+            a.push(data_type);
+            a.extend(data.to_le_bytes());
+            a
+        };
+        let mut el = vec![];
+        el.extend(RES_XML_START_ELEMENT_TYPE.to_le_bytes());
+        el.extend(16u16.to_le_bytes());
+        el.extend((16u32 + 20 + 2 * 20).to_le_bytes());
+        el.extend(1u32.to_le_bytes());
+        el.extend(NO_INDEX.to_le_bytes());
+        el.extend(NO_INDEX.to_le_bytes());
+        el.extend(0u32.to_le_bytes());
+        for v in [20u16, 20, 2, 0, 0, 0] {
+            el.extend(v.to_le_bytes());
+        }
+        el.extend(attr(1, NO_INDEX, 0x10, 1));
+        el.extend(attr(2, 3, TYPE_STRING, 3));
+
+        let mut xml = vec![];
+        xml.extend(RES_XML_TYPE.to_le_bytes());
+        xml.extend(8u16.to_le_bytes());
+        xml.extend(((8 + pool.len() + el.len()) as u32).to_le_bytes());
+        xml.extend(pool);
+        xml.extend(el);
+        xml
+    }
+
+    #[test]
+    fn test_find_attribute() {
+        let xml = manifest("ru.poas.englishwords");
+        let find = |el, attr| find_attribute(&xml, el, attr).unwrap();
+
+        assert_eq!(
+            find("manifest", "package"),
+            Some("ru.poas.englishwords".to_string())
+        );
+        assert_eq!(find("manifest", "versionCode"), None);
+        assert_eq!(find("manifest", "unknown"), None);
+        assert_eq!(find("application", "package"), None);
+        assert!(find_attribute(b"<manifest/>", "manifest", "package").is_err());
+    }
+}
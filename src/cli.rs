@@ -0,0 +1,452 @@
+//! Command line interface
+
+use std::{path::Path, time::Instant};
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use inquire::{Confirm, MultiSelect, Select, Text};
+
+use crate::{
+    apk::{detect_app, extract_db, extract_pictures, extract_sounds},
+    args, audio_cache_path, baseline_cache_path,
+    db::{Category, Word, DB},
+    db_cache_path,
+    deck::DeckWriter,
+    export::{export_words, Exporter},
+    filter_categories,
+    info::{App, Language, TrInfo},
+    inquire_autocomplete_path::FilePathCompleter,
+    json::JsonWriter,
+    media::{Pictures, Sounds, Tts},
+    pictures_cache_path,
+    profile::AppProfile,
+    query::{app_profile, supported_apps},
+    sounds_cache_path,
+    table::{TableOptions, TableWriter},
+    update::{Baseline, Collection, UpdateStats, Updater},
+    Format,
+};
+
+/// Minimum number of words to measure approximated export time
+const APPROX_BOUND: usize = 100;
+
+/// Name of output file without extension
+const DEFAULT_OUTPUT_FILE: &str = "reword";
+
+struct Input {
+    tr: TrInfo,
+    profile: &'static dyn AppProfile,
+    db: DB,
+    output_path: String,
+    format: Format,
+    pictures: Pictures,
+    sounds: Sounds,
+}
+
+/// Run command line interface
+pub fn run() -> Result<()> {
+    let args = args::Cli::parse();
+    let input = ask(&args)?;
+    let db = &input.db;
+
+    let total_words = db.words_count()?;
+    let words = db.list_words(input.profile, input.tr.clone())?;
+    if total_words > words.len() {
+        println!(
+            "Not all words are available for {} language, total words in database: {}",
+            input.tr.tr_lang.display(),
+            total_words
+        )
+    }
+
+    // select categories
+    let categories = db.list_categories(input.tr.tr_lang)?;
+    let selected = ask_categories(&args, categories.clone())?;
+    let words: Vec<_> = if let Some(selected) = &selected {
+        println!("All words count: {}", words.len());
+
+        let selected: Vec<_> = selected.iter().map(|c| c.id.clone()).collect();
+        filter_categories(words, &selected)
+    } else {
+        words
+    };
+    println!("Words to export: {}", words.len());
+
+    // values from app are saved on each export and compared on next update
+    let mut updater = match &args.update {
+        Some(_) if input.format != Format::Apkg => {
+            return Err(anyhow!("update is only supported for apkg"))
+        }
+        Some(collection) => {
+            let collection = Collection::open(collection)?;
+            println!("Notes found in collection: {}", collection.len());
+            Some(Updater::new(Some(collection), load_baseline(&input.tr)?))
+        }
+        None if input.format == Format::Apkg => Some(Updater::new(None, load_baseline(&input.tr)?)),
+        None => None,
+    };
+
+    let selected = selected.as_deref().unwrap_or(&categories);
+    let exporter = exporter(&args, &input, &categories, selected, updater.as_mut())?;
+    export(exporter, &words)?;
+    println!("File saved in {}", input.output_path);
+
+    if let Some(updater) = &updater {
+        updater.baseline().save(baseline_cache_path(&input.tr)?)?;
+        if args.update.is_some() {
+            print_update_stats(updater.stats());
+        }
+    }
+
+    Ok(())
+}
+
+/// Values of fields from previous export. If they cannot be read, fields that
+/// differ are considered as edited
+fn load_baseline(tr: &TrInfo) -> Result<Baseline> {
+    let path = baseline_cache_path(tr)?;
+    Ok(Baseline::load(&path).unwrap_or_else(|e| {
+        eprintln!(
+            "Failed to read values of previous export from {}: {e}",
+            path.display()
+        );
+        Baseline::default()
+    }))
+}
+
+fn print_update_stats(s: &UpdateStats) {
+    println!(
+        "New notes: {}, changed: {}, unchanged: {}",
+        s.new, s.changed, s.unchanged
+    );
+    if s.conflicts > 0 {
+        println!(
+            "Changes in app are not applied to {} notes with edited fields",
+            s.conflicts
+        );
+    }
+    if s.other_model > 0 {
+        println!(
+            "Skipped {} notes with other note type, they were exported by other version",
+            s.other_model
+        );
+    }
+}
+
+/// `selected` are categories selected for export
+fn exporter<'a>(
+    args: &args::Cli,
+    input: &'a Input,
+    categories: &[Category],
+    selected: &[Category],
+    updater: Option<&'a mut Updater>,
+) -> Result<Box<dyn Exporter + 'a>> {
+    let path = &input.output_path;
+    Ok(match input.format {
+        Format::Apkg => {
+            let mut deck = DeckWriter::new(path, input.profile, input.tr.clone())
+                .with_pictures(input.pictures.clone())
+                .with_sounds(input.sounds.clone())
+                .with_categories(categories);
+            if let Some(prefix) = &args.tag_prefix {
+                deck = deck.with_tag_prefix(prefix);
+            }
+            if args.subdecks {
+                deck = deck.with_subdecks(selected);
+            }
+            if let Some(command) = &args.tts_command {
+                let tts = Tts::new(command, input.tr.learn_lang, audio_cache_path()?)?;
+                deck = deck.with_tts(tts);
+            }
+            if let Some(updater) = updater {
+                deck = deck.with_update(updater);
+            }
+            Box::new(deck)
+        }
+        Format::Csv | Format::Tsv => {
+            let mut options = match input.format {
+                Format::Tsv => TableOptions::tsv(),
+                _ => TableOptions::csv(),
+            };
+            options.delimiter = args.delimiter.unwrap_or(options.delimiter);
+            options.header = !args.no_header;
+            options.escape = args.escape;
+            Box::new(TableWriter::new(path, input.profile, categories, options)?)
+        }
+        Format::Json | Format::Ndjson => Box::new(JsonWriter::new(
+            path,
+            categories,
+            input.format == Format::Ndjson,
+        )?),
+    })
+}
+
+/// Export words with timer
+fn export(exporter: Box<dyn Exporter + '_>, words: &[Word]) -> Result<()> {
+    let timer = Instant::now();
+    let total = words.len();
+    export_words(exporter, words, |done| {
+        if done == APPROX_BOUND && total > APPROX_BOUND {
+            let t = timer.elapsed() / APPROX_BOUND as u32 * total as u32;
+            println!("Approximated export time: {t:?}");
+        }
+        if done == total {
+            println!("Exported in {:?}, saving file", timer.elapsed());
+        }
+    })
+}
+
+/// Ask for:
+///
+/// - App
+/// - Path to APK file (if db for this app is not cached and db is not given)
+/// - Script (if app has several)
+/// - Translate language
+/// - Where to save exported collection
+///
+/// Values given in arguments are not asked. If path to APK is given, extract
+/// and cache db
+fn ask(args: &args::Cli) -> Result<Input> {
+    let apk = args.apk.as_ref().map(std::fs::read).transpose()?;
+    let detected = apk.as_deref().map(detect_app).transpose()?;
+    let app = match (args.app, detected) {
+        (Some(app), Some(detected)) => check_apk_app(app, detected)?,
+        (Some(app), None) => app,
+        (None, Some(detected)) => {
+            println!("Detected app: {}", detected.name());
+            detected
+        }
+        (None, None) => Select::new("App to import:", supported_apps()).prompt()?,
+    };
+    let profile = app_profile(app)?;
+
+    let current_dir = std::env::current_dir().unwrap();
+    let help_message = format!("Current directory: {}", current_dir.display());
+
+    let db_path = match (&args.db, apk) {
+        (Some(db_path), _) => db_path.clone(),
+        (None, Some(apk)) => {
+            let db_path = db_cache_path(app)?;
+            extract_apk_db(profile, &apk, &db_path)?;
+            cache_media(app, &apk, &DB::new(&db_path)?)?;
+            db_path
+        }
+        (None, None) => {
+            let db_path = db_cache_path(app)?;
+            if !db_path.exists() || args.no_cache {
+                let apk_path = Text::new("Path to APK file:")
+                    .with_autocomplete(FilePathCompleter::default())
+                    .with_help_message(&help_message)
+                    .prompt()?;
+                let apk = std::fs::read(apk_path)?;
+                check_apk_app(app, detect_app(&apk)?)?;
+                extract_apk_db(profile, &apk, &db_path)?;
+                cache_media(app, &apk, &DB::new(&db_path)?)?;
+            }
+            db_path
+        }
+    };
+
+    let db = DB::new(db_path)?;
+    db.check_schema()?;
+
+    let pictures = match &args.pictures {
+        Some(dir) => Pictures::from_dir(dir)?,
+        None if args.db.is_none() && pictures_cache_path(app)?.exists() => {
+            Pictures::from_dir(pictures_cache_path(app)?)?
+        }
+        None => Pictures::default(),
+    };
+    let sounds = match sounds_cache_path(app)? {
+        dir if args.db.is_none() && dir.exists() => Sounds::from_dir(dir)?,
+        _ => Sounds::default(),
+    };
+
+    let schema = db.schema()?;
+    let scripts = profile.learn_languages(&schema);
+    let learn_lang = match args.script {
+        Some(script) if !scripts.contains(&script) => {
+            return Err(anyhow!(
+                "{} script is not available for {}",
+                script.display(),
+                app.name()
+            ))
+        }
+        Some(script) => script,
+        None if scripts.len() == 1 => scripts[0],
+        None => Select::new("Script:", scripts).prompt()?,
+    };
+    let languages = profile.languages(&schema, learn_lang);
+    if languages.is_empty() {
+        return Err(anyhow!("no translation languages found in database"));
+    }
+    let tr_lang: Language = match args.lang {
+        Some(lang) if !languages.contains(&lang) => {
+            return Err(anyhow!(
+                "{} translation is not available for {}",
+                lang.display(),
+                app.name()
+            ))
+        }
+        Some(lang) => lang,
+        None => Select::new("Translate language:", languages).prompt()?,
+    };
+
+    let output_path = match &args.output {
+        Some(output_path) => output_path.clone(),
+        None => {
+            let format = args.format.unwrap_or(Format::Apkg);
+            Text::new("Path to exported file:")
+                .with_autocomplete(FilePathCompleter::default())
+                .with_help_message(&help_message)
+                .with_initial_value(&format!("{DEFAULT_OUTPUT_FILE}.{}", format.extension()))
+                .prompt()?
+        }
+    };
+    let format = args
+        .format
+        .or_else(|| Format::from_path(&output_path))
+        .unwrap_or(Format::Apkg);
+
+    Ok(Input {
+        tr: TrInfo {
+            app,
+            learn_lang,
+            tr_lang,
+        },
+        profile,
+        db,
+        output_path,
+        format,
+        pictures,
+        sounds,
+    })
+}
+
+/// Extract db from APK, report if it is not at known path
+fn extract_apk_db(profile: &dyn AppProfile, apk: &[u8], db_path: &Path) -> Result<()> {
+    if let Some(found) = extract_db(profile, apk, db_path)? {
+        eprintln!(
+            "Words database is not found at {}, using {found}",
+            profile.apk_db_path()
+        );
+    }
+    Ok(())
+}
+
+/// Extract pictures and audio from APK to cache, replacing previously cached
+fn cache_media(app: App, apk: &[u8], db: &DB) -> Result<()> {
+    let dir = pictures_cache_path(app)?;
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    let count = extract_pictures(apk, &dir, &db.list_pictures()?)?;
+    if count > 0 {
+        println!("Extracted {count} pictures from APK");
+    }
+
+    let dir = sounds_cache_path(app)?;
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    let names = db
+        .list_word_texts()?
+        .into_iter()
+        .flat_map(|(id, text)| Sounds::names(id, text.as_deref()))
+        .collect();
+    let count = extract_sounds(apk, &dir, &names)?;
+    if count > 0 {
+        println!("Extracted {count} audio files from APK");
+    }
+    Ok(())
+}
+
+/// Check that selected app is the same as app of APK
+fn check_apk_app(app: App, detected: App) -> Result<App> {
+    if app != detected {
+        return Err(anyhow!(
+            "APK is from {}, but selected app is {}",
+            detected.name(),
+            app.name()
+        ));
+    }
+    Ok(app)
+}
+
+/// Select categories from arguments or ask for them. `None` means all categories
+fn ask_categories(args: &args::Cli, categories: Vec<Category>) -> Result<Option<Vec<Category>>> {
+    if args.all_categories {
+        return Ok(None);
+    }
+    if !args.categories.is_empty() {
+        return find_categories(&args.categories, categories).map(Some);
+    }
+
+    if !Confirm::new("Select specific categories?")
+        .with_default(false)
+        .prompt()?
+    {
+        return Ok(None);
+    }
+
+    let total_categories = categories.len();
+    let result = MultiSelect::new("Select categories:", categories)
+        .with_all_selected_by_default()
+        .prompt()?;
+    if total_categories == result.len() {
+        Ok(None)
+    } else {
+        Ok(Some(result))
+    }
+}
+
+/// Find categories by id or name (case-insensitive)
+fn find_categories(queries: &[String], categories: Vec<Category>) -> Result<Vec<Category>> {
+    let mut result: Vec<Category> = Vec::with_capacity(queries.len());
+    for q in queries {
+        let found = categories
+            .iter()
+            .find(|c| c.id == *q || c.name.to_lowercase() == q.to_lowercase())
+            .ok_or_else(|| anyhow!("category not found: {q}"))?;
+        if !result.iter().any(|c| c.id == found.id) {
+            result.push(found.clone());
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_categories() {
+        let categories: Vec<_> = [("food", "Food"), ("travel", "Travel"), ("1", "Numbers")]
+            .into_iter()
+            .map(|(id, name)| Category {
+                id: id.to_string(),
+                name: name.to_string(),
+                words_count: 0,
+            })
+            .collect();
+        let table = [
+            (vec!["food"], Some(vec!["food"])),
+            // by name, case-insensitive
+            (vec!["TRAVEL"], Some(vec!["travel"])),
+            (vec!["numbers", "1"], Some(vec!["1"])),
+            (vec!["travel", "Food", "food"], Some(vec!["travel", "food"])),
+            (vec!["food", "unknown"], None),
+        ];
+        for (queries, expected) in table {
+            let queries: Vec<_> = queries.into_iter().map(String::from).collect();
+            let found = find_categories(&queries, categories.clone())
+                .ok()
+                .map(|c| c.into_iter().map(|c| c.id).collect::<Vec<_>>());
+            assert_eq!(
+                found,
+                expected.map(|e| e.into_iter().map(String::from).collect()),
+                "{queries:?}"
+            );
+        }
+    }
+}
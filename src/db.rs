@@ -1,12 +1,16 @@
-use std::{collections::HashMap, fmt::Display, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::Path,
+};
 
-use anyhow::Result;
-use rusqlite::Connection;
-use serde::Deserialize;
+use anyhow::{anyhow, Result};
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize, Serializer};
 
 use crate::{
     info::{Language, TrInfo},
-    query::{app_query_map, app_sql},
+    profile::AppProfile,
 };
 
 #[derive(Debug)]
@@ -15,12 +19,50 @@ pub struct DB {
 }
 
 impl DB {
+    /// Tables, from which words are extracted
+    pub const TABLES: [&'static str; 4] = ["word", "word_category", "category", "picture"];
+
     pub fn new(file: impl AsRef<Path>) -> Result<Self> {
         Ok(Self {
-            conn: Connection::open(file)?,
+            conn: Connection::open_with_flags(file, OpenFlags::SQLITE_OPEN_READ_ONLY)?,
         })
     }
+    /// Check that database is Reword's words database
+    pub fn check_schema(&self) -> Result<()> {
+        let mut st = self
+            .conn
+            .prepare("select name from sqlite_master where type = 'table'")?;
+        let tables = st
+            .query_map([], |r| r.get::<_, String>("name"))?
+            .collect::<rusqlite::Result<HashSet<_>>>()?;
+        let missing: Vec<_> = Self::TABLES
+            .into_iter()
+            .filter(|t| !tables.contains(*t))
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "not a Reword words database, missing tables: {}",
+                missing.join(", ")
+            ));
+        }
+        Ok(())
+    }
+    /// Read columns of tables, which depend on language
+    pub fn schema(&self) -> Result<Schema> {
+        Ok(Schema {
+            word: self.columns("word")?,
+            category: self.columns("category")?,
+        })
+    }
+    fn columns(&self, table: &str) -> Result<HashSet<String>> {
+        let mut st = self.conn.prepare(&format!("pragma table_info({table})"))?;
+        let columns = st
+            .query_map([], |r| r.get("name"))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(columns)
+    }
     pub fn list_categories(&self, lang: Language) -> Result<Vec<Category>> {
+        self.schema()?.check_language(lang)?;
         let mut st = self.conn.prepare(&Category::list_sql(lang))?;
         let categories: Vec<_> = st
             .query_map([], |r| {
@@ -41,14 +83,39 @@ impl DB {
         let mut st = self.conn.prepare("select count(*) as count from word")?;
         Ok(st.query_row([], |r| r.get("count"))?)
     }
-    pub fn list_words(&self, info: TrInfo) -> Result<Vec<Word>> {
-        let mut st = self.conn.prepare(&app_sql(info.clone()))?;
+    pub fn list_words(&self, profile: &dyn AppProfile, info: TrInfo) -> Result<Vec<Word>> {
+        let schema = self.schema()?;
+        schema.check_language(info.tr_lang)?;
+        let mut st = self.conn.prepare(&profile.sql(&info, &schema))?;
         let words = st
-            .query_map([], app_query_map(info.app))?
+            .query_map([], |r| profile.map_row(r))?
             .filter_map(|c| c.inspect_err(|e| eprintln!("failed to map word: {e}")).ok())
             .collect::<Vec<_>>();
         Ok(fold_categories(words))
     }
+    /// All pictures, including pictures of words without translation
+    pub fn list_pictures(&self) -> Result<Vec<Picture>> {
+        let mut st = self.conn.prepare("select source, source_id from picture")?;
+        let pictures = st
+            .query_map([], |r| {
+                Ok(Picture::new(r.get("source")?, r.get("source_id")?))
+            })?
+            .filter_map(|p| {
+                p.inspect_err(|e| eprintln!("failed to map picture: {e}"))
+                    .ok()
+                    .flatten()
+            })
+            .collect();
+        Ok(pictures)
+    }
+    /// Ids and texts of all words, including words without translation
+    pub fn list_word_texts(&self) -> Result<Vec<(i64, Option<String>)>> {
+        let mut st = self.conn.prepare("select id, word from word")?;
+        let words = st
+            .query_map([], |r| Ok((r.get("id")?, r.get("word")?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(words)
+    }
     /*pub fn delete_words(&mut self, ids: &[i64]) -> Result<()> {
         let tx = self.conn.transaction()?;
         for &i in ids {
@@ -59,7 +126,44 @@ impl DB {
     }*/
 }
 
-#[derive(Debug)]
+/// Columns of tables, which are used to find available languages
+#[derive(Debug, Default)]
+pub struct Schema {
+    word: HashSet<String>,
+    category: HashSet<String>,
+}
+
+impl Schema {
+    /// Languages, which have translations of words and names of categories
+    pub fn languages(&self, learn_lang: Language) -> Vec<Language> {
+        Language::ALL
+            .into_iter()
+            .filter(|&l| l != learn_lang && self.has_language(l))
+            .collect()
+    }
+    fn has_language(&self, lang: Language) -> bool {
+        self.word.contains(&lang.kind()) && self.category.contains(&Category::name_column(lang))
+    }
+    pub fn check_language(&self, lang: Language) -> Result<()> {
+        if !self.has_language(lang) {
+            return Err(anyhow!(
+                "{} translation is not available in database",
+                lang.display()
+            ));
+        }
+        Ok(())
+    }
+    pub fn has_word_column(&self, column: &str) -> bool {
+        self.word.contains(column)
+    }
+    /// Column with examples, if it exists
+    pub fn examples_column(&self, lang: Language) -> Option<String> {
+        let column = format!("examples_{}", lang.kind());
+        self.word.contains(&column).then_some(column)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Category {
     pub id: String,
     pub name: String,
@@ -80,7 +184,10 @@ impl Category {
          where c.is_custom = 0";
 
     fn list_sql(lang: Language) -> String {
-        Self::LIST_SQL.replace("{LANG}", &format!("c.name_{}", lang.kind()))
+        Self::LIST_SQL.replace("{LANG}", &format!("c.{}", Self::name_column(lang)))
+    }
+    fn name_column(lang: Language) -> String {
+        format!("name_{}", lang.kind())
     }
 }
 
@@ -90,7 +197,7 @@ impl Display for Category {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Word {
     pub id: i64,
     pub word: Option<String>,
@@ -115,8 +222,7 @@ fn fold_categories(words: Vec<Word>) -> Vec<Word> {
     map.into_values().collect()
 }
 
-#[allow(unused)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Picture {
     pub source: PictureSource,
     pub source_id: String,
@@ -129,6 +235,18 @@ impl Picture {
             source_id: source_id?,
         })
     }
+    /// Page of picture on source's site, if source is known
+    pub fn url(&self) -> Option<String> {
+        match self.source {
+            PictureSource::Pixabay => {
+                Some(format!("https://pixabay.com/photos/id-{}/", self.source_id))
+            }
+            PictureSource::Pexels => {
+                Some(format!("https://www.pexels.com/photo/{}/", self.source_id))
+            }
+            PictureSource::Other(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -148,6 +266,23 @@ impl From<&str> for PictureSource {
     }
 }
 
+impl PictureSource {
+    /// Human-readable name
+    pub fn name(&self) -> &str {
+        match self {
+            PictureSource::Pixabay => "Pixabay",
+            PictureSource::Pexels => "Pexels",
+            PictureSource::Other(s) => s.as_str(),
+        }
+    }
+}
+
+impl Serialize for PictureSource {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl Display for PictureSource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
@@ -159,7 +294,7 @@ impl Display for PictureSource {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Example {
     #[serde(rename(deserialize = "o"))]
     pub original: String,
@@ -193,6 +328,69 @@ fn hash2bold(s: &str) -> String {
         .1
 }
 
+/// Words database with the same structure as in Reword apps
+#[cfg(test)]
+pub mod fixture {
+    use std::path::Path;
+
+    use rusqlite::Connection;
+
+    use super::DB;
+    use crate::info::Language;
+
+    /// Write database from [`db`] to file
+    pub fn db_file(path: &Path, languages: &[Language]) {
+        db(languages)
+            .conn
+            .execute("vacuum into ?", [path.to_str().unwrap()])
+            .unwrap();
+    }
+
+    /// Create database with 2 words in 2 categories, translated to `languages`
+    pub fn db(languages: &[Language]) -> DB {
+        let conn = Connection::open_in_memory().unwrap();
+        let kinds: Vec<_> = languages.iter().map(|l| l.kind()).collect();
+        let word_columns: String = kinds
+            .iter()
+            .map(|k| format!(", {k} text, examples_{k} text"))
+            .collect();
+        let category_columns: String = kinds.iter().map(|k| format!(", name_{k} text")).collect();
+        conn.execute_batch(&format!(
+            "create table picture (id integer primary key, source text, source_id text);
+             create table word (
+               id integer primary key,
+               word text,
+               kanji text,
+               transcription text,
+               picture_id integer
+               {word_columns}
+             );
+             create table category (id text primary key, is_custom integer {category_columns});
+             create table word_category (word_id integer, category_id text);
+
+             insert into picture values (1, 'pixabay', '1234');
+             insert into word (id, word, kanji, transcription, picture_id)
+               values (1, 'apple', 'kanji', 'apple transcription', 1),
+                      (2, 'ticket', null, 'ticket transcription', null);
+             insert into category (id, is_custom)
+               values ('food', 0), ('travel', 0), ('custom', 1);
+             insert into word_category
+               values (1, 'food'), (1, 'travel'), (2, 'travel');"
+        ))
+        .unwrap();
+        for k in kinds {
+            conn.execute_batch(&format!(
+                "update word set
+                   {k} = word || ' {k}',
+                   examples_{k} = '[{{\"o\": \"#' || word || '#\", \"t\": \"{k}\"}}]';
+                 update category set name_{k} = id || ' {k}';"
+            ))
+            .unwrap();
+        }
+        DB { conn }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,4 +410,45 @@ mod tests {
             assert_eq!(hash2bold(input), expected);
         }
     }
+
+    #[test]
+    fn test_check_schema() {
+        let db = DB {
+            conn: Connection::open_in_memory().unwrap(),
+        };
+        assert!(db.check_schema().is_err());
+
+        for t in DB::TABLES {
+            db.conn
+                .execute(&format!("create table {t} (id integer)"), [])
+                .unwrap();
+        }
+        db.check_schema().unwrap();
+    }
+
+    #[test]
+    fn test_schema_languages() {
+        let db = DB {
+            conn: Connection::open_in_memory().unwrap(),
+        };
+        db.conn
+            .execute_batch(
+                "create table word (id integer, word text, eng text, rus text, deu text, examples_rus text);
+                 create table category (id text, name_eng text, name_rus text, name_fra text);",
+            )
+            .unwrap();
+        let schema = db.schema().unwrap();
+
+        assert_eq!(
+            schema.languages(Language::English),
+            [Language::Russian],
+            "learn language, languages without category names or translations are not available"
+        );
+        assert!(schema.check_language(Language::Deutsch).is_err());
+        assert_eq!(
+            schema.examples_column(Language::Russian).as_deref(),
+            Some("examples_rus")
+        );
+        assert_eq!(schema.examples_column(Language::English), None);
+    }
 }
@@ -1,10 +1,20 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
 use anyhow::Result;
-use genanki_rs::{Deck, Field, Model, Note, Template};
+use genanki_rs::{Deck, Field, Model, Note, Package, Template};
+use serde::Deserialize;
 
 use crate::{
-    db::{Example, Picture, Word},
+    db::{Category, Example, Picture, Word},
+    export::Exporter,
+    id::{category_deck_id, deck_id, stable_id},
     info::TrInfo,
-    query::{app_anki_fields, app_anki_values, app_model_id},
+    media::{Pictures, Sounds, Tts},
+    profile::AppProfile,
+    update::{Update, Updater},
 };
 
 const CSS: &str = ".card {
@@ -26,61 +36,231 @@ const EXAMPLES: &str = "
 {{examples}}
 {{/examples}}";
 
-pub struct DeckWriter {
+pub struct DeckWriter<'a> {
     model: Model,
+    model_id: i64,
     deck: Deck,
+    deck_id: i64,
+    deck_name: String,
+    /// Categories, which have subdecks, by id. If empty, all notes are added
+    /// to main deck
+    subdeck_categories: HashMap<String, Category>,
+    /// Subdecks by category's id
+    subdecks: BTreeMap<String, Deck>,
+    /// Category's id to name, for tags
+    category_names: HashMap<String, String>,
+    /// Prefix of tags, e.g. `reword::english`
+    tag_prefix: String,
+    profile: &'a dyn AppProfile,
     info: TrInfo,
+    path: String,
+    pictures: Pictures,
+    sounds: Sounds,
+    tts: Option<Tts>,
+    /// Files, which are added to package
+    media: BTreeSet<PathBuf>,
+    /// Only new and changed notes are exported, when collection is updated
+    updater: Option<&'a mut Updater>,
 }
 
-impl DeckWriter {
-    pub fn new(info: TrInfo) -> Self {
-        let fields = app_anki_fields(info.app);
+impl<'a> DeckWriter<'a> {
+    pub fn new(path: &str, profile: &'a dyn AppProfile, info: TrInfo) -> Self {
+        let fields = profile.anki_fields();
+        let name = format!(
+            "Reword {} - {}",
+            info.learn_lang.display(),
+            info.tr_lang.display()
+        );
+        let templates = [
+            (
+                format!("{} - {}", info.learn_lang.display(), info.tr_lang.display()),
+                fields.qfmt(),
+                fields.afmt(),
+            ),
+            (
+                format!("{} - {}", info.tr_lang.display(), info.learn_lang.display()),
+                fields.qfmt_rev(),
+                fields.afmt_rev(),
+            ),
+        ];
+        let model_id = model_id(&name, &fields, &templates);
         let model = Model::new(
-            app_model_id(info.app),
-            &format!("Reword {}", info.app.display()),
+            model_id,
+            &name,
             fields.names().into_iter().map(Field::new).collect(),
-            vec![
-                Template::new(&format!(
-                    "{} - {}",
-                    info.learn_lang.display(),
-                    info.tr_lang.display(),
-                ))
-                .qfmt(&fields.qfmt())
-                .afmt(&fields.afmt()),
-                Template::new(&format!(
-                    "{} - {}",
-                    info.tr_lang.display(),
-                    info.learn_lang.display(),
-                ))
-                .qfmt(&fields.qfmt_rev())
-                .afmt(&fields.afmt_rev()),
-            ],
+            templates
+                .iter()
+                .map(|(name, qfmt, afmt)| Template::new(name).qfmt(qfmt).afmt(afmt))
+                .collect(),
         )
         .css(CSS)
         .sort_field_index(AnkiFieldNames::sort_index());
-        let deck = Deck::new(
-            965781129384,
-            &format!("Reword {} - {}", info.app.display(), info.tr_lang.display()),
-            "",
-        );
-        Self { model, deck, info }
+        let deck_id = deck_id(&info);
+        let deck_name = format!("Reword {} - {}", info.app.display(), info.tr_lang.display());
+        Self {
+            model,
+            model_id,
+            deck: Deck::new(deck_id, &deck_name, ""),
+            deck_id,
+            deck_name,
+            subdeck_categories: HashMap::new(),
+            subdecks: BTreeMap::new(),
+            category_names: HashMap::new(),
+            tag_prefix: format!("reword::{}", slug(&info.app.display())),
+            profile,
+            info,
+            path: path.to_string(),
+            pictures: Pictures::default(),
+            sounds: Sounds::default(),
+            tts: None,
+            media: BTreeSet::new(),
+            updater: None,
+        }
+    }
+    /// Export only notes, which are not in updated collection or were changed
+    /// in app. User's edits of fields and tags are kept. Without collection
+    /// all notes are exported and only values from app are recorded
+    pub fn with_update(mut self, updater: &'a mut Updater) -> Self {
+        self.updater = Some(updater);
+        self
+    }
+    /// Use names of categories in tags instead of ids
+    pub fn with_categories(mut self, categories: &[Category]) -> Self {
+        self.category_names = categories
+            .iter()
+            .map(|c| (c.id.clone(), c.name.clone()))
+            .collect();
+        self
     }
-    pub fn word(&mut self, w: &Word) -> Result<()> {
-        self.deck.add_note(Note::new_with_options(
+    /// Prefix of tags, may be nested with `::`. Empty prefix means that tags
+    /// are just names of categories
+    pub fn with_tag_prefix(mut self, prefix: &str) -> Self {
+        self.tag_prefix = prefix.trim_end_matches("::").to_string();
+        self
+    }
+    /// Tag of category, e.g. `reword::english::food`
+    fn tag(&self, category_id: &str) -> String {
+        let name = self
+            .category_names
+            .get(category_id)
+            .map(|n| n.as_str())
+            .unwrap_or(category_id);
+        if self.tag_prefix.is_empty() {
+            slug(name)
+        } else {
+            format!("{}::{}", self.tag_prefix, slug(name))
+        }
+    }
+    /// Add notes to subdecks of `categories`, e.g. `Reword English -
+    /// Russian::Travel`. See [`subdeck_category`] for words in several
+    /// categories
+    pub fn with_subdecks(mut self, categories: &[Category]) -> Self {
+        self.subdeck_categories = categories
+            .iter()
+            .map(|c| (c.id.clone(), c.clone()))
+            .collect();
+        self
+    }
+    /// Add pictures to notes and package
+    pub fn with_pictures(mut self, pictures: Pictures) -> Self {
+        self.pictures = pictures;
+        self
+    }
+    /// Add audio of words to notes and package. Has priority over TTS
+    pub fn with_sounds(mut self, sounds: Sounds) -> Self {
+        self.sounds = sounds;
+        self
+    }
+    /// Generate audio of words with TTS
+    pub fn with_tts(mut self, tts: Tts) -> Self {
+        self.tts = Some(tts);
+        self
+    }
+    fn fields(&self, w: &Word, picture: &Option<PathBuf>, sound: &Option<PathBuf>) -> Vec<String> {
+        self.profile
+            .anki_values(w)
+            .list(media_name(picture).as_deref(), media_name(sound).as_deref())
+    }
+    /// How note should be exported, `None` if collection is not updated
+    fn check_update(&self, guid: &str, fields: &[String]) -> Option<Update> {
+        Some(self.updater.as_ref()?.check(guid, self.model_id, fields))
+    }
+}
+
+impl Exporter for DeckWriter<'_> {
+    fn word(&mut self, w: &Word) -> Result<()> {
+        let picture = w
+            .picture
+            .as_ref()
+            .and_then(|p| self.pictures.find(p))
+            .map(|p| p.to_path_buf());
+        let bundled = self.sounds.find(w).map(|p| p.to_path_buf());
+        let tts_text = w.word.as_deref().filter(|_| bundled.is_none());
+        let mut sound = bundled.or_else(|| Some(self.tts.as_ref()?.path(tts_text?)));
+
+        let guid = format!("reword-{}-{}", self.info.app.kind(), w.id);
+        let mut fields = self.fields(w, &picture, &sound);
+        let mut update = self.check_update(&guid, &fields);
+        let skipped = matches!(update, Some(Update::Unchanged { .. } | Update::OtherModel));
+        // audio is generated only for exported notes
+        if let (Some(tts), Some(text), false) = (&self.tts, tts_text, skipped) {
+            if let Err(e) = tts.audio(text) {
+                eprintln!("Audio is not added for \"{text}\": {e}");
+                sound = None;
+                fields = self.fields(w, &picture, &sound);
+                update = self.check_update(&guid, &fields);
+            }
+        }
+
+        let (fields, tags) = match (&mut self.updater, update) {
+            (Some(updater), Some(update)) => {
+                updater.record(&guid, &fields, &update);
+                match update {
+                    Update::New => (fields, None),
+                    Update::Changed { fields, tags, .. } => (fields, Some(tags)),
+                    Update::Unchanged { .. } | Update::OtherModel => return Ok(()),
+                }
+            }
+            _ => (fields, None),
+        };
+        self.media.extend(picture);
+        self.media.extend(sound);
+
+        let tags = tags.unwrap_or_else(|| w.category_ids.iter().map(|c| self.tag(c)).collect());
+        let note = Note::new_with_options(
             self.model.clone(),
-            app_anki_values(self.info.app, w)
-                .list()
-                .iter()
-                .map(|v| v.as_str())
-                .collect(),
+            fields.iter().map(|v| v.as_str()).collect(),
             None,
-            Some(w.category_ids.iter().map(|c| c.as_str()).collect()),
-            Some(&format!("reword-{}-{}", self.info.app.kind(), w.id)),
-        )?);
+            Some(tags.iter().map(|t| t.as_str()).collect()),
+            Some(&guid),
+        )?;
+        match subdeck_category(w, &self.subdeck_categories) {
+            Some(c) => self
+                .subdecks
+                .entry(c.id.clone())
+                .or_insert_with(|| {
+                    Deck::new(
+                        category_deck_id(self.deck_id, &c.id),
+                        &format!("{}::{}", self.deck_name, c.name),
+                        "",
+                    )
+                })
+                .add_note(note),
+            None => self.deck.add_note(note),
+        }
         Ok(())
     }
-    pub fn export(self, path: &str) -> Result<()> {
-        self.deck.write_to_file(path)?;
+    fn finish(self: Box<Self>) -> Result<()> {
+        let media: Vec<_> = self
+            .media
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        let decks = std::iter::once(self.deck)
+            .chain(self.subdecks.into_values())
+            .collect();
+        Package::new(decks, media.iter().map(|p| p.as_str()).collect())?
+            .write_to_file(&self.path)?;
         Ok(())
     }
 }
@@ -91,22 +271,26 @@ pub struct AnkiFields {
     pub reading: Option<String>,
     pub transcription: String,
     pub translate: Option<String>,
-    #[allow(unused)]
     pub picture: Option<Picture>,
     pub examples: Option<Vec<Example>>,
 }
 
 impl AnkiFields {
-    fn list(&self) -> Vec<String> {
+    /// Values of fields. `picture` and `sound` are names of files in package
+    fn list(&self, picture: Option<&str>, sound: Option<&str>) -> Vec<String> {
         vec![
             self.word.clone().unwrap_or_default(),
             self.reading.clone().unwrap_or_default(),
             self.translate.clone().unwrap_or_default(),
             self.transcription.clone(),
-            /*self.picture
-            .clone()
-            .map(|p| format!("{}:{}", p.source, p.source_id))
-            .unwrap_or_default(),*/
+            picture
+                .map(|p| format!("<img src=\"{p}\">"))
+                .unwrap_or_default(),
+            self.picture
+                .as_ref()
+                .map(picture_attribution)
+                .unwrap_or_default(),
+            sound.map(|s| format!("[sound:{s}]")).unwrap_or_default(),
             examples_to_html(self.examples.as_deref()),
         ]
     }
@@ -125,14 +309,18 @@ impl From<Word> for AnkiFields {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct AnkiFieldNames {
     pub word: String,
     pub reading: String,
     pub transcription: String,
     pub translate: String,
-    #[allow(unused)]
     pub picture: String,
+    /// Source of picture
+    pub attribution: String,
+    /// Pronunciation of word
+    pub sound: String,
 }
 
 impl Default for AnkiFieldNames {
@@ -143,6 +331,8 @@ impl Default for AnkiFieldNames {
             transcription: "Transcription".to_string(),
             translate: "Translate".to_string(),
             picture: "Picture".to_string(),
+            attribution: "Attribution".to_string(),
+            sound: "Sound".to_string(),
         }
     }
 }
@@ -159,7 +349,8 @@ impl AnkiFieldNames {
             &self.reading,
             &self.translate,
             &self.transcription,
-            // &self.picture,
+            &self.picture,
+            &self.sound,
         ]
         .iter()
         .map(|f| Self::field(f))
@@ -167,8 +358,9 @@ impl AnkiFieldNames {
         .join("<br>\n");
 
         format!(
-            "{}\n<hr id=\"answer\">\n{back}{EXAMPLES}",
-            Self::field("FrontSide")
+            "{}\n<hr id=\"answer\">\n{back}{}{EXAMPLES}",
+            Self::field("FrontSide"),
+            self.attribution(),
         )
     }
     fn afmt_rev(&self) -> String {
@@ -176,7 +368,8 @@ impl AnkiFieldNames {
             &self.word,
             &self.reading,
             &self.transcription,
-            // &self.picture,
+            &self.picture,
+            &self.sound,
         ]
         .iter()
         .map(|f| Self::field(f))
@@ -184,10 +377,16 @@ impl AnkiFieldNames {
         .join("<br>\n");
 
         format!(
-            "{}\n<hr id=\"answer\">\n{back}{EXAMPLES}",
-            Self::field("FrontSide")
+            "{}\n<hr id=\"answer\">\n{back}{}{EXAMPLES}",
+            Self::field("FrontSide"),
+            self.attribution(),
         )
     }
+    /// Attribution is shown only when picture has source
+    fn attribution(&self) -> String {
+        let name = &self.attribution;
+        format!("\n{{{{#{name}}}}}<br><small>{{{{{name}}}}}</small>{{{{/{name}}}}}")
+    }
     fn field(name: &str) -> String {
         format!("{{{{{name}}}}}")
     }
@@ -200,12 +399,65 @@ impl AnkiFieldNames {
             self.reading.as_str(),
             self.translate.as_str(),
             self.transcription.as_str(),
-            // &self.picture,
+            self.picture.as_str(),
+            self.attribution.as_str(),
+            self.sound.as_str(),
             EXAMPLES_FIELD,
         ]
     }
 }
 
+/// Name of file in package
+fn media_name(path: &Option<PathBuf>) -> Option<String> {
+    path.as_deref()
+        .and_then(Path::file_name)
+        .map(|n| n.to_string_lossy().to_string())
+}
+
+/// Id of model is derived from everything in model, so models with different
+/// fields or templates do not share id. Templates are `(name, qfmt, afmt)`
+fn model_id(name: &str, fields: &AnkiFieldNames, templates: &[(String, String, String)]) -> i64 {
+    let mut parts = vec![name];
+    parts.extend(fields.names());
+    for (name, qfmt, afmt) in templates {
+        parts.extend([name.as_str(), qfmt.as_str(), afmt.as_str()]);
+    }
+    parts.push(CSS);
+    stable_id(&parts)
+}
+
+/// Lower case name, where all characters except letters and digits are
+/// replaced with `-`, e.g. `Food & drinks` -> `food-drinks`
+fn slug(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Category of word's subdeck. When word is in several categories, the most
+/// specific one is used, i.e. with the fewest words, ties are broken by
+/// category's id. `None` if word is not in any of `categories`
+fn subdeck_category<'c>(
+    w: &Word,
+    categories: &'c HashMap<String, Category>,
+) -> Option<&'c Category> {
+    w.category_ids
+        .iter()
+        .filter_map(|id| categories.get(id))
+        .min_by_key(|c| (c.words_count, &c.id))
+}
+
+/// Link to picture's page, or plain text if source is unknown
+fn picture_attribution(picture: &Picture) -> String {
+    let text = format!("{} {}", picture.source.name(), picture.source_id);
+    match picture.url() {
+        Some(url) => format!("<a href=\"{url}\">{text}</a>"),
+        None => text,
+    }
+}
+
 fn examples_to_html(examples: Option<&[Example]>) -> String {
     let Some(examples) = examples else {
         // todo: do not use cfg
@@ -227,7 +479,15 @@ fn examples_to_html(examples: Option<&[Example]>) -> String {
 
 #[cfg(test)]
 mod tests {
-    use crate::db::PictureSource;
+    use rusqlite::{params, Connection};
+
+    use crate::{
+        db::{fixture, PictureSource},
+        info::{App, Language},
+        query::app_profile,
+        test_dir::TestDir,
+        update::{Baseline, Collection},
+    };
 
     use super::*;
 
@@ -238,8 +498,19 @@ mod tests {
         let translate = "translate";
         let transcription = "transcription";
         let picture = "picture";
+        let attribution = "attribution";
+        let sound = "sound";
 
-        let expected = vec![word, reading, translate, transcription, EXAMPLES_FIELD];
+        let expected = vec![
+            word,
+            reading,
+            translate,
+            transcription,
+            picture,
+            attribution,
+            sound,
+            EXAMPLES_FIELD,
+        ];
 
         let names = AnkiFieldNames {
             word: word.to_string(),
@@ -247,6 +518,8 @@ mod tests {
             transcription: transcription.to_string(),
             translate: translate.to_string(),
             picture: picture.to_string(),
+            attribution: attribution.to_string(),
+            sound: sound.to_string(),
         };
         let fields = AnkiFields {
             word: Some(word.to_string()),
@@ -271,8 +544,144 @@ mod tests {
         );
         assert_eq!(names, expected.clone(), "field's names are broken");
 
-        let fields = fields.list();
+        let fields = fields.list(Some(picture), Some(sound));
         let fields: Vec<_> = fields.iter().map(|f| f.as_str()).collect();
+        let mut expected = expected;
+        let picture = format!("<img src=\"{picture}\">");
+        expected[4] = &picture;
+        let attribution = "<a href=\"https://pixabay.com/photos/id-asdf/\">Pixabay asdf</a>";
+        expected[5] = attribution;
+        let sound = format!("[sound:{sound}]");
+        expected[6] = &sound;
         assert_eq!(fields, expected, "field's values are broken");
+
+        let other = Picture {
+            source: PictureSource::Other("unsplash".to_string()),
+            source_id: "1".to_string(),
+        };
+        assert_eq!(picture_attribution(&other), "unsplash 1");
+    }
+
+    #[test]
+    fn test_model_id() {
+        let fields = AnkiFieldNames::default();
+        let templates = [("a".to_string(), fields.qfmt(), fields.afmt())];
+        let id = model_id("model", &fields, &templates);
+        assert_eq!(
+            id,
+            model_id("model", &fields, &templates),
+            "id is not stable"
+        );
+
+        let other = AnkiFieldNames {
+            word: "Other".to_string(),
+            ..AnkiFieldNames::default()
+        };
+        assert_ne!(id, model_id("model", &other, &templates));
+        let templates = [("a".to_string(), fields.qfmt_rev(), fields.afmt())];
+        assert_ne!(id, model_id("model", &fields, &templates));
+    }
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("Food"), "food");
+        assert_eq!(slug(" Food & drinks "), "food-drinks");
+        assert_eq!(slug("Еда и напитки"), "еда-и-напитки");
+        assert_eq!(slug("a::b"), "a-b");
+    }
+
+    #[test]
+    fn test_subdeck_category() {
+        let categories: HashMap<_, _> = [("food", 10), ("travel", 5), ("city", 5)]
+            .into_iter()
+            .map(|(id, words_count)| {
+                let c = Category {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    words_count,
+                };
+                (c.id.clone(), c)
+            })
+            .collect();
+        let word = |ids: &[&str]| Word {
+            id: 1,
+            word: None,
+            transcription: String::new(),
+            picture: None,
+            reading: None,
+            translate: None,
+            examples: None,
+            category_ids: ids.iter().map(|c| c.to_string()).collect(),
+        };
+        let find = |ids| subdeck_category(&word(ids), &categories).map(|c| c.id.as_str());
+
+        assert_eq!(find(&["food", "travel"]), Some("travel"));
+        assert_eq!(find(&["travel", "city", "food"]), Some("city"));
+        assert_eq!(find(&["food", "other"]), Some("food"));
+        assert_eq!(find(&["other"]), None);
+    }
+
+    /// Values from plain export are used on first update after app's changes
+    #[test]
+    fn test_update_after_export() {
+        let db = fixture::db(&[Language::English, Language::Russian]);
+        let profile = app_profile(App::Russian).unwrap();
+        let info = TrInfo {
+            app: App::Russian,
+            learn_lang: Language::Russian,
+            tr_lang: Language::English,
+        };
+        let mut words = db.list_words(profile, info.clone()).unwrap();
+        words.sort_by_key(|w| w.id);
+
+        // plain export, notes are imported to Anki as is
+        let mut recorder = Updater::new(None, Baseline::default());
+        let mut deck = DeckWriter::new("", profile, info.clone()).with_update(&mut recorder);
+        for w in &words {
+            deck.word(w).unwrap();
+        }
+        let model_id = deck.model_id;
+        let notes: Vec<_> = words
+            .iter()
+            .map(|w| (format!("reword-rus-{}", w.id), deck.fields(w, &None, &None)))
+            .collect();
+        drop(deck);
+        assert_eq!(recorder.stats().new, 2);
+
+        let dir = TestDir::new();
+        let baseline = dir.join("baseline.json");
+        recorder.baseline().save(&baseline).unwrap();
+        let path = dir.join("collection.anki2");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute(
+            "create table notes (id integer, guid text, mid integer, flds text, tags text)",
+            [],
+        )
+        .unwrap();
+        for (i, (guid, fields)) in notes.iter().enumerate() {
+            conn.execute(
+                "insert into notes values (?, ?, ?, ?, '')",
+                params![i as i64, guid, model_id, fields.join("\x1f")],
+            )
+            .unwrap();
+        }
+        drop(conn);
+
+        // app changed translation of the first word
+        words[0].translate = Some("apple changed".to_string());
+        let mut updater = Updater::new(
+            Some(Collection::open(&path).unwrap()),
+            Baseline::load(&baseline).unwrap(),
+        );
+        let mut deck = DeckWriter::new("", profile, info).with_update(&mut updater);
+        for w in &words {
+            deck.word(w).unwrap();
+        }
+        drop(deck);
+
+        let stats = updater.stats();
+        assert_eq!(stats.changed, 1);
+        assert_eq!(stats.unchanged, 1);
+        assert_eq!(stats.conflicts, 0);
     }
 }
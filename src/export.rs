@@ -0,0 +1,32 @@
+//! Common interface of export formats
+
+use anyhow::Result;
+
+use crate::db::Word;
+
+/// Writer of exported words. Words are passed one by one between
+/// [`Exporter::begin`] and [`Exporter::finish`]
+pub trait Exporter {
+    /// Called before the first word, e.g. to write header
+    fn begin(&mut self) -> Result<()> {
+        Ok(())
+    }
+    fn word(&mut self, w: &Word) -> Result<()>;
+    /// Called after the last word, writes everything that is left
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// Pass all words to exporter. `progress` is called after each word with
+/// number of already exported words
+pub fn export_words(
+    mut exporter: Box<dyn Exporter + '_>,
+    words: &[Word],
+    mut progress: impl FnMut(usize),
+) -> Result<()> {
+    exporter.begin()?;
+    for (i, w) in words.iter().enumerate() {
+        exporter.word(w)?;
+        progress(i + 1);
+    }
+    exporter.finish()
+}
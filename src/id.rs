@@ -0,0 +1,65 @@
+//! Stable ids of Anki objects and hashes, which do not change between builds
+
+use crate::info::TrInfo;
+
+/// Stable positive id, derived from `parts`
+pub fn stable_id(parts: &[&str]) -> i64 {
+    (fnv1a(parts.join("\0").as_bytes()) >> 1) as i64
+}
+
+/// Id of deck, different for each app and languages. It is the same with and
+/// without subdecks, so the same deck is updated
+pub fn deck_id(info: &TrInfo) -> i64 {
+    stable_id(&[
+        "deck",
+        &info.app.kind(),
+        &info.learn_lang.kind(),
+        &info.tr_lang.kind(),
+    ])
+}
+
+/// Stable id for subdeck of category
+pub fn category_deck_id(deck_id: i64, category_id: &str) -> i64 {
+    stable_id(&["category", &deck_id.to_string(), category_id])
+}
+
+/// 64-bit FNV-1a hash. Unlike std hashers, it is stable between builds
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::info::{App, Language};
+
+    use super::*;
+
+    #[test]
+    fn test_deck_id() {
+        let info = TrInfo {
+            app: App::English,
+            learn_lang: Language::English,
+            tr_lang: Language::Russian,
+        };
+        let id = deck_id(&info);
+        assert_eq!(id, deck_id(&info.clone()));
+
+        let other = TrInfo {
+            tr_lang: Language::Deutsch,
+            ..info
+        };
+        assert_ne!(id, deck_id(&other));
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+    }
+}
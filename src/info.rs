@@ -1,8 +1,9 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
-// Order of fields are important and used for calculating Anki model's id
-#[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
 pub enum App {
     Chinese,
     Czech,
@@ -22,10 +23,10 @@ pub enum App {
     Turkish,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
 pub enum Language {
     ChineseSimplified,
-    #[allow(dead_code)]
     ChineseTraditional,
     Czech,
     /// German
@@ -55,8 +56,23 @@ pub struct TrInfo {
 }
 
 impl App {
-    pub const SUPPORTED: [App; 4] = [App::English, App::Deutsch, App::Japanese, App::Russian];
-
+    pub const ALL: [App; 15] = [
+        App::Chinese,
+        App::Czech,
+        App::Deutsch,
+        App::Dutch,
+        App::English,
+        App::Finnish,
+        App::French,
+        App::Italian,
+        App::Japanese,
+        App::Korean,
+        App::Polish,
+        App::Portuguese,
+        App::Russian,
+        App::Spanish,
+        App::Turkish,
+    ];
     pub fn kind(&self) -> String {
         let s = match self {
             Self::Chinese => "ch",
@@ -97,6 +113,29 @@ impl App {
         };
         s.to_owned()
     }
+    /// Detect app by Android package name, e.g. `ru.poas.englishwords`
+    pub fn from_package(package: &str) -> Option<Self> {
+        let name = package.strip_prefix("ru.poas.")?;
+        let app = match name {
+            _ if name.starts_with("chinese") => Self::Chinese,
+            _ if name.starts_with("czech") => Self::Czech,
+            _ if name.starts_with("german") || name.starts_with("deutsch") => Self::Deutsch,
+            _ if name.starts_with("dutch") => Self::Dutch,
+            _ if name.starts_with("english") => Self::English,
+            _ if name.starts_with("finnish") => Self::Finnish,
+            _ if name.starts_with("french") => Self::French,
+            _ if name.starts_with("italian") => Self::Italian,
+            _ if name.starts_with("japanese") => Self::Japanese,
+            _ if name.starts_with("korean") => Self::Korean,
+            _ if name.starts_with("polish") => Self::Polish,
+            _ if name.starts_with("portuguese") => Self::Portuguese,
+            _ if name.starts_with("russian") => Self::Russian,
+            _ if name.starts_with("spanish") => Self::Spanish,
+            _ if name.starts_with("turkish") => Self::Turkish,
+            _ => return None,
+        };
+        Some(app)
+    }
     pub fn name(&self) -> String {
         match self {
             Self::English => "ReWord: Learn English Language".to_string(),
@@ -114,6 +153,25 @@ impl Display for App {
 }
 
 impl Language {
+    pub const ALL: [Language; 16] = [
+        Language::ChineseSimplified,
+        Language::ChineseTraditional,
+        Language::Czech,
+        Language::Deutsch,
+        Language::Dutch,
+        Language::English,
+        Language::Finnish,
+        Language::French,
+        Language::Italian,
+        Language::Japanese,
+        Language::Korean,
+        Language::Polish,
+        Language::Portuguese,
+        Language::Russian,
+        Language::Spanish,
+        Language::Turkish,
+    ];
+
     pub fn kind(&self) -> String {
         let s = match self {
             Self::ChineseSimplified => "zhs",
@@ -156,6 +214,47 @@ impl Language {
         };
         s.to_owned()
     }
+    /// ISO 639-1 code, e.g. for TTS engines
+    pub fn code(&self) -> String {
+        let s = match self {
+            Self::ChineseSimplified | Self::ChineseTraditional => "zh",
+            Self::Czech => "cs",
+            Self::Deutsch => "de",
+            Self::Dutch => "nl",
+            Self::English => "en",
+            Self::Finnish => "fi",
+            Self::French => "fr",
+            Self::Italian => "it",
+            Self::Japanese => "ja",
+            Self::Korean => "ko",
+            Self::Polish => "pl",
+            Self::Portuguese => "pt",
+            Self::Russian => "ru",
+            Self::Spanish => "es",
+            Self::Turkish => "tr",
+        };
+        s.to_owned()
+    }
+}
+
+/// Parse from kind (e.g. "eng") or display name (e.g. "English"), case-insensitive
+impl FromStr for App {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.kind().eq_ignore_ascii_case(s) || a.display().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown app: {s}"))
+    }
+}
+
+impl TryFrom<String> for App {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
 }
 
 impl Display for Language {
@@ -164,6 +263,26 @@ impl Display for Language {
     }
 }
 
+/// Parse from kind (e.g. "rus") or display name (e.g. "Russian"), case-insensitive
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|l| l.kind().eq_ignore_ascii_case(s) || l.display().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown language: {s}"))
+    }
+}
+
+impl TryFrom<String> for Language {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
 impl From<App> for Language {
     fn from(value: App) -> Self {
         match value {
@@ -185,3 +304,56 @@ impl From<App> for Language {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_app() {
+        let table = [
+            ("eng", Some(App::English)),
+            ("ENG", Some(App::English)),
+            ("english", Some(App::English)),
+            ("ch", Some(App::Chinese)),
+            ("Chinese", Some(App::Chinese)),
+            ("fr", Some(App::French)),
+            // language's kind is not app's kind
+            ("fra", None),
+            ("ReWord: Learn English Language", None),
+            ("", None),
+        ];
+        for (input, expected) in table {
+            assert_eq!(input.parse().ok(), expected, "{input}");
+        }
+        for app in App::ALL {
+            assert_eq!(app.kind().parse(), Ok(app));
+            assert_eq!(app.display().parse(), Ok(app));
+        }
+    }
+
+    #[test]
+    fn test_parse_language() {
+        let table = [
+            ("rus", Some(Language::Russian)),
+            ("Russian", Some(Language::Russian)),
+            ("zhs", Some(Language::ChineseSimplified)),
+            // ambiguous name is simplified script
+            ("chinese", Some(Language::ChineseSimplified)),
+            ("Chinese traditional", Some(Language::ChineseTraditional)),
+            ("zht", Some(Language::ChineseTraditional)),
+            ("fra", Some(Language::French)),
+            // app's kind is not language's kind
+            ("fr", None),
+            ("ch", None),
+            ("", None),
+        ];
+        for (input, expected) in table {
+            assert_eq!(input.parse().ok(), expected, "{input}");
+        }
+        for lang in Language::ALL {
+            assert_eq!(lang.kind().parse(), Ok(lang));
+            assert_eq!(lang.display().parse(), Ok(lang));
+        }
+    }
+}
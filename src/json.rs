@@ -0,0 +1,132 @@
+//! Export of words as JSON array or newline-delimited JSON
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    db::{Category, Word},
+    export::Exporter,
+};
+
+pub struct JsonWriter {
+    out: BufWriter<File>,
+    /// Category's id to name
+    categories: HashMap<String, String>,
+    /// Write one word per line instead of array
+    ndjson: bool,
+    /// No words are written yet
+    first: bool,
+}
+
+#[derive(Serialize)]
+struct JsonWord<'a> {
+    #[serde(flatten)]
+    word: &'a Word,
+    category_names: Vec<&'a str>,
+}
+
+impl JsonWriter {
+    pub fn new(path: impl AsRef<Path>, categories: &[Category], ndjson: bool) -> Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+            categories: categories
+                .iter()
+                .map(|c| (c.id.clone(), c.name.clone()))
+                .collect(),
+            ndjson,
+            first: true,
+        })
+    }
+}
+
+impl Exporter for JsonWriter {
+    fn begin(&mut self) -> Result<()> {
+        if !self.ndjson {
+            write!(self.out, "[")?;
+        }
+        Ok(())
+    }
+    fn word(&mut self, w: &Word) -> Result<()> {
+        let word = JsonWord {
+            word: w,
+            category_names: w
+                .category_ids
+                .iter()
+                .filter_map(|c| self.categories.get(c).map(|n| n.as_str()))
+                .collect(),
+        };
+        if self.ndjson {
+            serde_json::to_writer(&mut self.out, &word)?;
+            writeln!(self.out)?;
+            return Ok(());
+        }
+        if !self.first {
+            write!(self.out, ",")?;
+        }
+        self.first = false;
+        writeln!(self.out)?;
+        serde_json::to_writer(&mut self.out, &word)?;
+        Ok(())
+    }
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        if !self.ndjson {
+            writeln!(self.out, "\n]")?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::fixture,
+        export::export_words,
+        info::{App, Language, TrInfo},
+        query::app_profile,
+        test_dir::TestDir,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_json_writer() {
+        let db = fixture::db(&[Language::English]);
+        let info = TrInfo {
+            app: App::French,
+            learn_lang: Language::French,
+            tr_lang: Language::English,
+        };
+        let words = db
+            .list_words(app_profile(App::French).unwrap(), info)
+            .unwrap();
+        let categories = db.list_categories(Language::English).unwrap();
+
+        let dir = TestDir::new();
+        for ndjson in [false, true] {
+            let path = dir.join(if ndjson { "words.ndjson" } else { "words.json" });
+            let json = JsonWriter::new(&path, &categories, ndjson).unwrap();
+            export_words(Box::new(json), &words, |_| {}).unwrap();
+
+            let data = std::fs::read_to_string(&path).unwrap();
+            let values: Vec<serde_json::Value> = if ndjson {
+                data.lines()
+                    .map(|l| serde_json::from_str(l).unwrap())
+                    .collect()
+            } else {
+                serde_json::from_str(&data).unwrap()
+            };
+            assert_eq!(values.len(), 2);
+            let apple = values.iter().find(|v| v["id"] == 1).unwrap();
+            assert_eq!(apple["category_names"].as_array().unwrap().len(), 2);
+            assert_eq!(apple["picture"]["source"], "pixabay");
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! Convert words lists from [Reword](https://reword.app) apps to Anki decks
+//!
+//! ```no_run
+//! use rewordapp2anki::{extract_words, write_package, Input, Language, Options};
+//!
+//! let input = Input::Apk("english.apk".into());
+//! let words = extract_words(&input, &Options::new(Language::Russian))?;
+//! write_package(&words, "english.apkg")?;
+//! # anyhow::Ok(())
+//! ```
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    apk::{detect_app, extract_db},
+    db::DB,
+    deck::DeckWriter,
+    export::export_words,
+    query::app_profile,
+};
+
+pub use crate::{
+    db::{Category, Example, Picture, PictureSource, Word},
+    info::{App, Language, TrInfo},
+};
+
+mod apk;
+mod args;
+mod axml;
+mod cli;
+mod db;
+mod deck;
+mod export;
+mod id;
+mod info;
+mod inquire_autocomplete_path;
+mod json;
+mod media;
+mod profile;
+mod query;
+mod table;
+#[cfg(test)]
+mod test_dir;
+mod update;
+
+#[doc(hidden)]
+pub use cli::run;
+
+/// Where words are read from
+#[derive(Debug, Clone)]
+pub enum Input {
+    /// APK or app bundle, app is detected from it
+    Apk(PathBuf),
+    /// Already extracted words database
+    Db { path: PathBuf, app: App },
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Translation language
+    pub tr_lang: Language,
+    /// Script of learned language, for apps which have several. By default
+    /// app's language is used
+    pub learn_lang: Option<Language>,
+    /// Ids of categories to export. If empty, all words are exported
+    pub categories: Vec<String>,
+}
+
+impl Options {
+    pub fn new(tr_lang: Language) -> Self {
+        Self {
+            tr_lang,
+            learn_lang: None,
+            categories: vec![],
+        }
+    }
+}
+
+/// Format of exported file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Anki package
+    Apkg,
+    Csv,
+    Tsv,
+    Json,
+    /// Newline-delimited JSON, one word per line
+    Ndjson,
+}
+
+impl Format {
+    /// Detect format by file's extension
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        path.as_ref().extension()?.to_str()?.parse().ok()
+    }
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Apkg => "apkg",
+            Self::Csv => "csv",
+            Self::Tsv => "tsv",
+            Self::Json => "json",
+            Self::Ndjson => "ndjson",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::Apkg, Self::Csv, Self::Tsv, Self::Json, Self::Ndjson]
+            .into_iter()
+            .find(|f| f.extension().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown format: {s}"))
+    }
+}
+
+/// Extracted words with information about translation
+#[derive(Debug, Clone)]
+pub struct Words {
+    pub info: TrInfo,
+    /// All categories of app, names are in translation language
+    pub categories: Vec<Category>,
+    pub words: Vec<Word>,
+}
+
+/// Extract words from APK or database. APK is extracted to temporary file, cache
+/// is not used
+pub fn extract_words(input: &Input, options: &Options) -> Result<Words> {
+    match input {
+        Input::Apk(path) => {
+            let apk = std::fs::read(path)?;
+            let app = detect_app(&apk)?;
+            let db_path = temp_path(&format!("{}.db", app.kind()));
+            let words = extract_db(app_profile(app)?, &apk, &db_path)
+                .and_then(|_| read_words(app, &db_path, options));
+            // db does not exist, if extraction failed
+            let _ = std::fs::remove_file(&db_path);
+            words
+        }
+        Input::Db { path, app } => read_words(*app, path, options),
+    }
+}
+
+fn read_words(app: App, db_path: &Path, options: &Options) -> Result<Words> {
+    let profile = app_profile(app)?;
+    let db = DB::new(db_path)?;
+    db.check_schema()?;
+
+    let learn_lang = options.learn_lang.unwrap_or(app.into());
+    if !profile.learn_languages(&db.schema()?).contains(&learn_lang) {
+        return Err(anyhow!(
+            "{} is not available for {}",
+            learn_lang.display(),
+            app.name()
+        ));
+    }
+    let info = TrInfo {
+        app,
+        learn_lang,
+        tr_lang: options.tr_lang,
+    };
+
+    let words = db.list_words(profile, info.clone())?;
+    Ok(Words {
+        categories: db.list_categories(info.tr_lang)?,
+        info,
+        words: filter_categories(words, &options.categories),
+    })
+}
+
+/// Keep only words, which are in any of `categories`. Empty `categories` means
+/// all categories
+pub(crate) fn filter_categories(words: Vec<Word>, categories: &[String]) -> Vec<Word> {
+    if categories.is_empty() {
+        return words;
+    }
+    let categories: HashSet<_> = categories.iter().collect();
+    words
+        .into_iter()
+        .filter(|w| w.category_ids.iter().any(|c| categories.contains(c)))
+        .collect()
+}
+
+/// Write words to Anki package
+pub fn write_package(words: &Words, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let path = path
+        .to_str()
+        .ok_or_else(|| anyhow!("path is not valid UTF-8: {}", path.display()))?;
+    let deck = DeckWriter::new(path, app_profile(words.info.app)?, words.info.clone())
+        .with_categories(&words.categories);
+    export_words(Box::new(deck), &words.words, |_| {})
+}
+
+/// Unique path in temporary directory, `name` is added to the end
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "{}-{}-{}-{name}",
+        env!("CARGO_PKG_NAME"),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Where extracted db of app is cached
+pub(crate) fn db_cache_path(app: App) -> Result<PathBuf> {
+    let db_path = cache_dir()?.join("db");
+    std::fs::create_dir_all(&db_path)?;
+    let db_path = db_path.join(format!("{}.db", app.kind()));
+    Ok(db_path)
+}
+
+/// Where pictures extracted from APK are cached
+pub(crate) fn pictures_cache_path(app: App) -> Result<PathBuf> {
+    Ok(cache_dir()?.join("pictures").join(app.kind()))
+}
+
+/// Where audio extracted from APK is cached
+pub(crate) fn sounds_cache_path(app: App) -> Result<PathBuf> {
+    Ok(cache_dir()?.join("sounds").join(app.kind()))
+}
+
+/// Where audio generated by TTS is cached
+pub(crate) fn audio_cache_path() -> Result<PathBuf> {
+    Ok(cache_dir()?.join("audio"))
+}
+
+/// Where values of fields from last export to apkg are saved, see
+/// [`update::Baseline`]
+pub(crate) fn baseline_cache_path(info: &TrInfo) -> Result<PathBuf> {
+    Ok(cache_dir()?.join("exports").join(format!(
+        "{}-{}-{}.json",
+        info.app.kind(),
+        info.learn_lang.kind(),
+        info.tr_lang.kind()
+    )))
+}
+
+fn cache_dir() -> Result<PathBuf> {
+    Ok(dirs::cache_dir()
+        .ok_or_else(|| anyhow!("cannot determine cache directory"))?
+        .join(env!("CARGO_PKG_NAME")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{db::fixture, test_dir::TestDir};
+
+    use super::*;
+
+    #[test]
+    fn test_extract_words() {
+        let dir = TestDir::new();
+        let path = dir.join("words.db");
+        fixture::db_file(&path, &[Language::English]);
+
+        let input = Input::Db {
+            path,
+            app: App::French,
+        };
+        let words = extract_words(&input, &Options::new(Language::English)).unwrap();
+        assert_eq!(words.words.len(), 2);
+        assert_eq!(words.categories.len(), 2);
+
+        let options = Options {
+            categories: vec!["food".to_string()],
+            ..Options::new(Language::English)
+        };
+        let words = extract_words(&input, &options).unwrap();
+        assert_eq!(words.words.len(), 1);
+
+        let options = Options::new(Language::Russian);
+        assert!(extract_words(&input, &options).is_err());
+    }
+
+    #[test]
+    fn test_temp_path() {
+        assert_ne!(temp_path("eng.db"), temp_path("eng.db"));
+    }
+}
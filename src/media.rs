@@ -0,0 +1,305 @@
+//! Media files (pictures, audio) attached to exported notes
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    db::{Picture, Word},
+    id::fnv1a,
+    info::Language,
+};
+
+/// Extensions of files which are considered as pictures
+pub const PICTURE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "webp", "gif"];
+
+/// Extensions of files which are considered as audio
+pub const SOUND_EXTENSIONS: [&str; 6] = ["mp3", "ogg", "opus", "wav", "m4a", "aac"];
+
+/// Pictures from local directory. File is matched to word's picture by name
+/// without extension: `{source}_{source_id}` (e.g. `pixabay_1234.jpg`) or
+/// just `{source_id}`
+#[derive(Debug, Clone, Default)]
+pub struct Pictures {
+    /// File's name without extension to path
+    files: HashMap<String, PathBuf>,
+}
+
+impl Pictures {
+    /// Read list of pictures in directory, subdirectories are not checked
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            files: files_by_stem(dir.as_ref(), |p| is_picture(p), str::to_string)?,
+        })
+    }
+    pub fn find(&self, picture: &Picture) -> Option<&Path> {
+        Self::names(picture)
+            .iter()
+            .find_map(|n| self.files.get(n))
+            .map(|p| p.as_path())
+    }
+    /// Names of files without extension, which are matched to `picture`, in
+    /// order of preference
+    pub fn names(picture: &Picture) -> [String; 2] {
+        [
+            format!("{}_{}", picture.source, picture.source_id),
+            picture.source_id.clone(),
+        ]
+    }
+}
+
+/// Audio files from local directory, e.g. extracted from APK. File is matched
+/// to word by name without extension: word's id (e.g. `123.mp3`) or word's
+/// text in lower case, with spaces or underscores (e.g. `ice_cream.mp3`)
+#[derive(Debug, Clone, Default)]
+pub struct Sounds {
+    /// File's name without extension, in lower case, to path
+    files: HashMap<String, PathBuf>,
+}
+
+impl Sounds {
+    /// Read list of audio files in directory, subdirectories are not checked
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            files: files_by_stem(dir.as_ref(), |p| is_sound(p), str::to_lowercase)?,
+        })
+    }
+    pub fn find(&self, w: &Word) -> Option<&Path> {
+        Self::names(
+            w.id,
+            [w.word.as_deref(), w.reading.as_deref()]
+                .into_iter()
+                .flatten(),
+        )
+        .iter()
+        .find_map(|n| self.files.get(n))
+        .map(|p| p.as_path())
+    }
+    /// Names of files without extension, in lower case, which are matched to
+    /// word with `id` and `texts`, in order of preference
+    pub fn names<'a>(id: i64, texts: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        let mut names = vec![id.to_string()];
+        for text in texts {
+            let text = text.trim().to_lowercase();
+            let underscored = text.replace(' ', "_");
+            names.extend([text, underscored]);
+        }
+        names
+    }
+}
+
+/// Audio generated by external TTS command
+#[derive(Debug, Clone)]
+pub struct Tts {
+    /// Program and its arguments with placeholders
+    command: Vec<String>,
+    lang: Language,
+    /// Where generated audio is cached
+    cache_dir: PathBuf,
+}
+
+impl Tts {
+    /// Placeholders in `command`: `{text}`, `{lang}` (ISO 639-1 code) and
+    /// `{output}` (path to WAV file to write). Command is split by whitespace
+    /// and placeholders are replaced in each argument, so text with spaces is
+    /// passed as one argument. Without `{text}`, text is written to stdin
+    pub fn new(command: &str, lang: Language, cache_dir: impl Into<PathBuf>) -> Result<Self> {
+        let command: Vec<_> = command.split_whitespace().map(ToOwned::to_owned).collect();
+        if command.is_empty() {
+            return Err(anyhow!("TTS command is empty"));
+        }
+        if !command.iter().any(|a| a.contains("{output}")) {
+            return Err(anyhow!("TTS command should contain {{output}} placeholder"));
+        }
+        Ok(Self {
+            command,
+            lang,
+            cache_dir: cache_dir.into(),
+        })
+    }
+    /// Where audio with `text` is cached, it may be not generated yet
+    pub fn path(&self, text: &str) -> PathBuf {
+        let key = format!("{}\0{}\0{text}", self.command.join(" "), self.lang.code());
+        self.cache_dir.join(format!(
+            "reword-tts-{}-{:016x}.wav",
+            self.lang.code(),
+            fnv1a(key.as_bytes())
+        ))
+    }
+    /// Path to audio with `text`. Audio is generated, if it is not cached
+    pub fn audio(&self, text: &str) -> Result<PathBuf> {
+        let path = self.path(text);
+        if path.exists() {
+            return Ok(path);
+        }
+
+        std::fs::create_dir_all(&self.cache_dir)?;
+        // audio is written to separate file to not cache partially written audio
+        let tmp_path = path.with_extension("tmp.wav");
+        let args: Vec<_> = self
+            .command
+            .iter()
+            .map(|a| {
+                a.replace("{text}", text)
+                    .replace("{lang}", &self.lang.code())
+                    .replace("{output}", &tmp_path.to_string_lossy())
+            })
+            .collect();
+        let stdin = !self.command.iter().any(|a| a.contains("{text}"));
+        let mut child = Command::new(&args[0])
+            .args(&args[1..])
+            .stdin(if stdin { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run TTS command {}", args[0]))?;
+        if let Some(mut input) = child.stdin.take() {
+            input.write_all(text.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let mut message = format!("TTS command failed for \"{text}\", {}", output.status);
+            if !stderr.trim().is_empty() {
+                message = format!("{message}\n{}", stderr.trim());
+            }
+            return Err(anyhow!(message));
+        }
+        std::fs::rename(&tmp_path, &path)
+            .with_context(|| format!("TTS command did not write audio for \"{text}\""))?;
+        Ok(path)
+    }
+}
+
+pub fn is_picture(path: impl AsRef<Path>) -> bool {
+    has_extension(path.as_ref(), &PICTURE_EXTENSIONS)
+}
+
+pub fn is_sound(path: impl AsRef<Path>) -> bool {
+    has_extension(path.as_ref(), &SOUND_EXTENSIONS)
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| extensions.contains(&e.to_lowercase().as_str()))
+}
+
+/// Files in `dir`, for which `is_match` is true, by `key` of name without
+/// extension. If several files have the same key, the first by path is used
+fn files_by_stem(
+    dir: &Path,
+    is_match: fn(&Path) -> bool,
+    key: fn(&str) -> String,
+) -> Result<HashMap<String, PathBuf>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    let mut files = HashMap::new();
+    for path in paths {
+        if !path.is_file() || !is_match(&path) {
+            continue;
+        }
+        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+            files.entry(key(stem)).or_insert_with(|| path.clone());
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{db::PictureSource, test_dir::TestDir};
+
+    use super::*;
+
+    #[test]
+    fn test_find_pictures() {
+        let dir = TestDir::new();
+        for name in ["pixabay_1.jpg", "2.PNG", "3.txt"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let pictures = Pictures::from_dir(&dir).unwrap();
+        let find = |source, id: &str| {
+            pictures
+                .find(&Picture {
+                    source,
+                    source_id: id.to_string(),
+                })
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+        };
+        assert_eq!(
+            find(PictureSource::Pixabay, "1").as_deref(),
+            Some("pixabay_1.jpg")
+        );
+        assert_eq!(find(PictureSource::Pexels, "1"), None);
+        assert_eq!(find(PictureSource::Pexels, "2").as_deref(), Some("2.PNG"));
+        assert_eq!(find(PictureSource::Pexels, "3"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_tts() {
+        let dir = TestDir::new();
+        let cache_dir = dir.join("cache");
+
+        let tts = Tts::new("cp {text} {output}", Language::English, &cache_dir).unwrap();
+        let source = dir.join("source");
+        std::fs::write(&source, b"audio").unwrap();
+        let text = source.to_string_lossy();
+
+        let path = tts.audio(&text).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"audio");
+        // cached audio is not generated again
+        std::fs::remove_file(&source).unwrap();
+        assert_eq!(tts.audio(&text).unwrap(), path);
+
+        assert!(tts.audio("missing").is_err());
+        assert!(Tts::new("espeak-ng {text}", Language::English, &cache_dir).is_err());
+
+        // without {text}, text is written to stdin
+        let tts = Tts::new("tee {output}", Language::English, &cache_dir).unwrap();
+        let path = tts.audio("from stdin").unwrap();
+        assert_eq!(path, tts.path("from stdin"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"from stdin");
+    }
+
+    #[test]
+    fn test_find_sounds() {
+        let dir = TestDir::new();
+        for name in ["1.mp3", "Apple.ogg", "ice_cream.wav", "2.txt"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let sounds = Sounds::from_dir(&dir).unwrap();
+        let find = |id, word: &str| {
+            let w = Word {
+                id,
+                word: Some(word.to_string()),
+                transcription: String::new(),
+                picture: None,
+                reading: None,
+                translate: None,
+                examples: None,
+                category_ids: vec![],
+            };
+            sounds
+                .find(&w)
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+        };
+        assert_eq!(find(1, "one").as_deref(), Some("1.mp3"));
+        assert_eq!(find(3, "apple").as_deref(), Some("Apple.ogg"));
+        assert_eq!(find(4, "Ice cream").as_deref(), Some("ice_cream.wav"));
+        assert_eq!(find(2, "two"), None);
+    }
+}
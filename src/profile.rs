@@ -0,0 +1,325 @@
+//! App profiles describe how words are extracted from app's database
+//!
+//! Built-in profiles are in `profiles` directory and are embedded at compile
+//! time. User profiles are loaded from `<config dir>/rewordapp2anki/profiles/*.toml`
+//! and replace built-in profiles for the same app
+
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::Result;
+use rusqlite::Row;
+use serde::Deserialize;
+
+use crate::{
+    db::{Example, Picture, Schema, Word},
+    deck::{AnkiFieldNames, AnkiFields},
+    info::{App, Language, TrInfo},
+};
+
+const BUILTIN: [&str; 15] = [
+    include_str!("../profiles/ch.toml"),
+    include_str!("../profiles/cz.toml"),
+    include_str!("../profiles/deu.toml"),
+    include_str!("../profiles/du.toml"),
+    include_str!("../profiles/eng.toml"),
+    include_str!("../profiles/fin.toml"),
+    include_str!("../profiles/fr.toml"),
+    include_str!("../profiles/it.toml"),
+    include_str!("../profiles/jap.toml"),
+    include_str!("../profiles/kor.toml"),
+    include_str!("../profiles/pol.toml"),
+    include_str!("../profiles/por.toml"),
+    include_str!("../profiles/rus.toml"),
+    include_str!("../profiles/sp.toml"),
+    include_str!("../profiles/tur.toml"),
+];
+
+const WORDS_SQL: &str = "
+select
+   w.id,
+   {WORD} as word,
+   {READING} as reading,
+   {TRANSCRIPTION} as transcription,
+   {LANG} as translate,
+   {EXAMPLES} as examples,
+   wc.category_id,
+   p.source as picture_source,
+   p.source_id as picture_source_id
+ from word w
+ join word_category wc
+   on w.id = wc.word_id
+ full outer join picture p
+   on p.id = w.picture_id
+ where translate is not null";
+
+/// Everything that is specific to app
+pub trait AppProfile {
+    /// Where sqlite db is located inside APK
+    fn apk_db_path(&self) -> &str;
+    /// SQL query for extracting words. Names of fields should match names in
+    /// [`AppProfile::map_row`]
+    fn sql(&self, info: &TrInfo, schema: &Schema) -> String;
+    fn map_row(&self, r: &Row<'_>) -> rusqlite::Result<Word> {
+        Ok(Word {
+            id: r.get("id")?,
+            word: r.get("word")?,
+            transcription: r.get("transcription")?,
+            picture: Picture::new(r.get("picture_source")?, r.get("picture_source_id")?),
+            reading: r.get("reading")?,
+            translate: r.get("translate")?,
+            examples: Example::from_db(r.get("examples")?),
+            category_ids: vec![r.get("category_id")?],
+        })
+    }
+    fn anki_fields(&self) -> AnkiFieldNames;
+    fn anki_values(&self, w: &Word) -> AnkiFields {
+        w.clone().into()
+    }
+    /// Languages (scripts), which can be learned in app
+    fn learn_languages(&self, schema: &Schema) -> Vec<Language>;
+    /// Translation languages, which are available in db
+    fn languages(&self, schema: &Schema, learn_lang: Language) -> Vec<Language>;
+}
+
+/// Profile, which is described in TOML file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// App's kind, e.g. `eng`
+    pub app: App,
+    /// Where sqlite db is located inside APK
+    #[serde(default = "default_db_path")]
+    pub db_path: String,
+    /// Translation languages. If empty, all languages found in db are used
+    #[serde(default)]
+    pub languages: Vec<Language>,
+    #[serde(default)]
+    pub columns: Columns,
+    #[serde(default)]
+    pub fields: AnkiFieldNames,
+    /// Scripts of learned language, if app has several
+    #[serde(default)]
+    pub scripts: Vec<Script>,
+}
+
+/// SQL expressions for word's values, table `word` is available as `w`
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Columns {
+    pub word: String,
+    pub reading: String,
+    pub transcription: String,
+    /// If not set, `examples_{lang}` column is used when it exists
+    pub examples: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Script {
+    pub lang: Language,
+    /// Column in `word` table, without which script is not available
+    pub requires: Option<String>,
+    /// Replaces `columns.word` for this script
+    pub word: Option<String>,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Self {
+            word: "w.word".to_string(),
+            reading: "null".to_string(),
+            transcription: "w.transcription".to_string(),
+            examples: None,
+        }
+    }
+}
+
+fn default_db_path() -> String {
+    "res/raw/englishwordsdb".to_string()
+}
+
+impl AppProfile for Profile {
+    fn apk_db_path(&self) -> &str {
+        &self.db_path
+    }
+    fn sql(&self, info: &TrInfo, schema: &Schema) -> String {
+        let word = self
+            .scripts
+            .iter()
+            .find(|s| s.lang == info.learn_lang)
+            .and_then(|s| s.word.as_deref())
+            .unwrap_or(&self.columns.word);
+        let examples = match &self.columns.examples {
+            Some(examples) => examples.clone(),
+            None => schema
+                .examples_column(info.tr_lang)
+                .map(|c| format!("w.{c}"))
+                .unwrap_or_else(|| "null".to_string()),
+        };
+        WORDS_SQL
+            .replace("{WORD}", word)
+            .replace("{READING}", &self.columns.reading)
+            .replace("{TRANSCRIPTION}", &self.columns.transcription)
+            .replace("{LANG}", &format!("w.{}", info.tr_lang.kind()))
+            .replace("{EXAMPLES}", &examples)
+    }
+    fn anki_fields(&self) -> AnkiFieldNames {
+        self.fields.clone()
+    }
+    fn learn_languages(&self, schema: &Schema) -> Vec<Language> {
+        if self.scripts.is_empty() {
+            return vec![self.app.into()];
+        }
+        self.scripts
+            .iter()
+            .filter(|s| match &s.requires {
+                Some(column) => schema.has_word_column(column),
+                None => true,
+            })
+            .map(|s| s.lang)
+            .collect()
+    }
+    fn languages(&self, schema: &Schema, learn_lang: Language) -> Vec<Language> {
+        schema
+            .languages(learn_lang)
+            .into_iter()
+            .filter(|l| self.languages.is_empty() || self.languages.contains(l))
+            .collect()
+    }
+}
+
+/// Profile of app, `None` if app is not supported
+pub fn profile(app: App) -> Option<&'static Profile> {
+    static PROFILES: OnceLock<Vec<Profile>> = OnceLock::new();
+    // tests should not depend on developer's profiles
+    let user_dir = || {
+        if cfg!(test) {
+            None
+        } else {
+            user_profiles_dir()
+        }
+    };
+    PROFILES
+        .get_or_init(|| load(user_dir().as_deref()))
+        .iter()
+        .find(|p| p.app == app)
+}
+/// Built-in profiles, replaced by user's profiles from `user_dir`
+fn load(user_dir: Option<&Path>) -> Vec<Profile> {
+    let mut profiles: Vec<Profile> = BUILTIN
+        .iter()
+        .map(|s| toml::from_str(s).expect("built-in profile should be valid"))
+        .collect();
+    for profile in user_dir.map(user_profiles).unwrap_or_default() {
+        profiles.retain(|p| p.app != profile.app);
+        profiles.push(profile);
+    }
+    profiles
+}
+fn user_profiles_dir() -> Option<PathBuf> {
+    Some(
+        dirs::config_dir()?
+            .join(env!("CARGO_PKG_NAME"))
+            .join("profiles"),
+    )
+}
+fn user_profiles(dir: &Path) -> Vec<Profile> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+        .filter_map(|p| {
+            read_profile(&p)
+                .inspect_err(|e| eprintln!("failed to load profile {}: {e}", p.display()))
+                .ok()
+        })
+        .collect()
+}
+
+fn read_profile(path: &Path) -> Result<Profile> {
+    Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{db::fixture, test_dir::TestDir};
+
+    use super::*;
+
+    #[test]
+    fn test_builtin_profiles() {
+        let profiles: Vec<Profile> = BUILTIN.iter().map(|s| toml::from_str(s).unwrap()).collect();
+        for app in App::ALL {
+            assert_eq!(
+                profiles.iter().filter(|p| p.app == app).count(),
+                1,
+                "there should be one built-in profile for {app:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_user_profiles() {
+        let dir = TestDir::new();
+        std::fs::write(
+            dir.join("cz.toml"),
+            "app = \"cz\"\ndb_path = \"res/raw/czech\"",
+        )
+        .unwrap();
+        std::fs::write(dir.join("invalid.toml"), "app = \"unknown\"").unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let profiles = load(Some(&dir));
+        assert_eq!(profiles.len(), BUILTIN.len());
+        let cz: Vec<_> = profiles.iter().filter(|p| p.app == App::Czech).collect();
+        assert_eq!(cz.len(), 1);
+        assert_eq!(cz[0].db_path, "res/raw/czech");
+    }
+
+    #[test]
+    fn test_scripts() {
+        // "kanji" column stands for column with traditional hanzi
+        let profile: Profile = toml::from_str(
+            r#"
+            app = "ch"
+            [[scripts]]
+            lang = "zhs"
+            [[scripts]]
+            lang = "zht"
+            requires = "kanji"
+            word = "w.kanji"
+            "#,
+        )
+        .unwrap();
+        let db = fixture::db(&[Language::English]);
+        let schema = db.schema().unwrap();
+        assert_eq!(
+            profile.learn_languages(&schema),
+            [Language::ChineseSimplified, Language::ChineseTraditional]
+        );
+
+        let words = |learn_lang| {
+            let info = TrInfo {
+                app: App::Chinese,
+                learn_lang,
+                tr_lang: Language::English,
+            };
+            let mut words = db.list_words(&profile, info).unwrap();
+            words.sort_by_key(|w| w.id);
+            words.into_iter().map(|w| w.word).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            words(Language::ChineseSimplified),
+            [Some("apple".to_string()), Some("ticket".to_string())]
+        );
+        assert_eq!(
+            words(Language::ChineseTraditional),
+            [Some("kanji".to_string()), None]
+        );
+    }
+}
@@ -1,194 +1,166 @@
-pub use func::*;
-
-use crate::info::TrInfo;
+use std::fmt::Display;
 
-mod func {
-    use rusqlite::Row;
+pub use func::*;
 
-    use crate::{
-        db::{Example, Picture, Word},
-        deck::{AnkiFieldNames, AnkiFields},
-        info::{App, Language, TrInfo},
-    };
+use crate::info::App;
 
-    use super::*;
+/// App is known, but extracting words from it is not implemented
+#[derive(Debug)]
+pub struct UnsupportedApp(pub App);
 
-    /// Where sqlite db is located inside apk
-    pub fn app_apk_db_path(app: App) -> &'static str {
-        match app {
-            App::Russian => "res/kv",
-            _ => "res/raw/englishwordsdb",
-        }
-    }
-
-    /// Pseudo-random and stable id for anki collection
-    pub fn app_model_id(app: App) -> i64 {
-        10964854234530 + app as i64
+impl Display for UnsupportedApp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "app is not yet supported: {}", self.0.name())
     }
+}
 
-    /// SQL query for extracting words
-    //
-    // Names of fields matches names in App::map_row
-    pub fn app_sql(info: TrInfo) -> String {
-        match info.app {
-            App::Deutsch => words_common(info),
-            App::English => words_common(info),
-            App::Japanese => jap::words(info),
-            App::Russian => words_common(info),
-            _ => todo!("app not yet supported"),
-        }
-    }
+impl std::error::Error for UnsupportedApp {}
 
-    // Separate function for case if some app will require different extracting
-    pub fn app_query_map(_app: App) -> impl FnMut(&Row<'_>) -> rusqlite::Result<Word> {
-        map_row
-    }
-
-    fn map_row(r: &Row<'_>) -> rusqlite::Result<Word> {
-        Ok(Word {
-            id: r.get("id")?,
-            word: r.get("word")?,
-            transcription: r.get("transcription")?,
-            picture: Picture::new(r.get("picture_source")?, r.get("picture_source_id")?),
-            reading: r.get("reading")?,
-            translate: r.get("translate")?,
-            examples: Example::from_db(r.get("examples")?),
-            category_ids: vec![r.get("category_id")?],
-        })
-    }
+mod func {
+    use crate::profile::{profile, AppProfile};
 
-    /*pub fn app_sql_params(info: TrInfo) -> Vec<String> {
-        match info.app {
-            App::English => eng::params(info),
-            App::Japanese => jap::params(info),
-            _ => todo!("app not yet supported"),
-        }
-    }*/
+    use super::*;
 
-    pub fn app_anki_fields(app: App) -> AnkiFieldNames {
-        match app {
-            App::Japanese => jap::anki_fields(),
-            _ => AnkiFieldNames::default(),
-        }
+    /// App is supported, when it has a profile
+    pub fn app_supported(app: App) -> bool {
+        profile(app).is_some()
     }
 
-    pub fn app_anki_values(app: App, w: &Word) -> AnkiFields {
-        match app {
-            App::Japanese => jap::anki_values(w),
-            _ => w.clone().into(),
-        }
+    /// Apps, from which words can be extracted, English first
+    pub fn supported_apps() -> Vec<App> {
+        let mut apps: Vec<_> = App::ALL.into_iter().filter(|&a| app_supported(a)).collect();
+        apps.sort_by_key(|&a| a != App::English);
+        apps
     }
 
-    /// List of languages supported by app for learning
-    pub fn app_languages(app: App) -> &'static [Language] {
-        match app {
-            App::Deutsch => &deu::LANGUAGES,
-            App::English => &eng::LANGUAGES,
-            App::Japanese => &jap::LANGUAGES,
-            App::Russian => &rus::LANGUAGES,
-            _ => todo!("app not yet supported"),
+    pub fn app_profile(app: App) -> Result<&'static dyn AppProfile, UnsupportedApp> {
+        match profile(app) {
+            Some(p) => Ok(p),
+            None => Err(UnsupportedApp(app)),
         }
     }
 }
 
-const COMMON_WORDS_SQL: &str = "
-select
-   w.id,
-   w.word,
-   w.transcription,
-   null as reading,
-   {LANG} as translate,
-   {EXAMPLES} as examples,
-   wc.category_id,
-   p.source as picture_source,
-   p.source_id as picture_source_id
- from word w
- join word_category wc
-   on w.id = wc.word_id
- full outer join picture p
-   on p.id = w.picture_id
- where translate is not null";
-
-fn words_common(info: TrInfo) -> String {
-    let kind = info.tr_lang.kind();
-    COMMON_WORDS_SQL
-        .replace("{LANG}", &format!("w.{kind}"))
-        .replace("{EXAMPLES}", &format!("w.examples_{kind}"))
-}
-
-mod deu {
-    use crate::info::Language;
-
-    pub const LANGUAGES: [Language; 2] = [Language::English, Language::Russian];
-}
-
-mod eng {
-    use crate::info::Language;
-
-    pub const LANGUAGES: [Language; 10] = [
-        Language::ChineseSimplified,
-        Language::Deutsch,
-        Language::Dutch,
-        Language::French,
-        Language::Italian,
-        Language::Japanese,
-        Language::Korean,
-        Language::Russian,
-        Language::Spanish,
-        Language::Turkish,
-    ];
-}
-
-mod jap {
+#[cfg(test)]
+mod tests {
+    use super::*;
     use crate::{
-        db::Word,
-        deck::{AnkiFieldNames, AnkiFields},
-        info::{Language, TrInfo},
+        db::fixture,
+        deck::DeckWriter,
+        export::Exporter,
+        info::{App, Language, TrInfo},
     };
 
-    const WORDS: &str = "select
-           w.id,
-           w.kanji as word,
-           w.word as reading,
-           w.transcription,
-           {LANG} as translate,
-           null as examples,
-           wc.category_id,
-           p.source as picture_source,
-           p.source_id as picture_source_id
-         from word w
-         join word_category wc
-           on w.id = wc.word_id
-         full outer join picture p
-           on p.id = w.picture_id
-         where translate is not null";
-
-    pub fn words(info: TrInfo) -> String {
-        WORDS.replace("{LANG}", &format!("w.{}", info.tr_lang.kind()))
-    }
-
-    pub const LANGUAGES: [Language; 2] = [Language::English, Language::Russian];
-
-    pub fn anki_fields() -> AnkiFieldNames {
-        AnkiFieldNames {
-            word: "Kanji".to_string(),
-            reading: "Kana".to_string(),
-            transcription: "Romaji".to_string(),
-            ..Default::default()
+    /// Apps differ by location of db in APK and by learned language, which is
+    /// not available for translation. Words of each app are converted to notes
+    #[test]
+    fn test_app_profiles() {
+        let table = [
+            (App::Czech, "res/raw/englishwordsdb", Language::Czech),
+            (App::Dutch, "res/raw/englishwordsdb", Language::Dutch),
+            (App::Finnish, "res/raw/englishwordsdb", Language::Finnish),
+            (App::French, "res/raw/englishwordsdb", Language::French),
+            (App::Italian, "res/raw/englishwordsdb", Language::Italian),
+            (App::Polish, "res/raw/englishwordsdb", Language::Polish),
+            (
+                App::Portuguese,
+                "res/raw/englishwordsdb",
+                Language::Portuguese,
+            ),
+            (App::Spanish, "res/raw/englishwordsdb", Language::Spanish),
+            (App::Turkish, "res/raw/englishwordsdb", Language::Turkish),
+            (App::Russian, "res/kv", Language::Russian),
+        ];
+        for (app, db_path, learn_lang) in table {
+            let db = fixture::db(&[Language::English, learn_lang]);
+            let schema = db.schema().unwrap();
+            let profile = app_profile(app).unwrap();
+
+            assert_eq!(profile.apk_db_path(), db_path, "{app:?}");
+            assert_eq!(profile.learn_languages(&schema), [learn_lang], "{app:?}");
+            assert_eq!(
+                profile.languages(&schema, learn_lang),
+                [Language::English],
+                "{app:?}"
+            );
+
+            let info = TrInfo {
+                app,
+                learn_lang,
+                tr_lang: Language::English,
+            };
+            let mut words = db.list_words(profile, info.clone()).unwrap();
+            words.sort_by_key(|w| w.id);
+            let translations: Vec<_> = words.iter().map(|w| w.translate.as_deref()).collect();
+            assert_eq!(
+                translations,
+                [Some("apple eng"), Some("ticket eng")],
+                "{app:?}"
+            );
+
+            let mut deck = DeckWriter::new("", profile, info);
+            for w in &words {
+                deck.word(w).unwrap();
+            }
         }
     }
 
-    pub fn anki_values(w: &Word) -> AnkiFields {
-        AnkiFields {
-            word: w.word.clone().or(w.reading.clone()),
-            reading: w.word.clone().and(w.reading.clone()),
-            ..w.clone().into()
+    /// Issue template for new apps should list only not supported apps
+    #[test]
+    fn test_issue_template_apps() {
+        let template = include_str!("../.github/ISSUE_TEMPLATE/1-new-app.yml");
+        for app in App::ALL {
+            assert_eq!(
+                template.contains(&app.name()),
+                !app_supported(app),
+                "issue template is out of sync for {}",
+                app.name()
+            );
         }
     }
-}
 
-mod rus {
-    use crate::info::Language;
+    #[test]
+    fn test_japanese_app() {
+        let db = fixture::db(&[Language::English]);
+        let mut words = db
+            .list_words(
+                app_profile(App::Japanese).unwrap(),
+                TrInfo {
+                    app: App::Japanese,
+                    learn_lang: Language::Japanese,
+                    tr_lang: Language::English,
+                },
+            )
+            .unwrap();
+        words.sort_by_key(|w| w.id);
+
+        assert_eq!(words[0].word.as_deref(), Some("kanji"));
+        assert_eq!(words[0].reading.as_deref(), Some("apple"));
+        assert_eq!(words[1].word.as_deref(), Some("ticket"));
+        assert_eq!(words[1].reading, None);
+    }
 
-    pub const LANGUAGES: [Language; 3] = [Language::Deutsch, Language::English, Language::French];
+    #[test]
+    fn test_chinese_app() {
+        let db = fixture::db(&[Language::English]);
+        let profile = app_profile(App::Chinese).unwrap();
+        assert_eq!(
+            profile.learn_languages(&db.schema().unwrap()),
+            [Language::ChineseSimplified]
+        );
+
+        let info = TrInfo {
+            app: App::Chinese,
+            learn_lang: Language::ChineseSimplified,
+            tr_lang: Language::English,
+        };
+        let mut words = db.list_words(profile, info).unwrap();
+        words.sort_by_key(|w| w.id);
+        assert_eq!(words[0].word.as_deref(), Some("apple"));
+        assert_eq!(words[0].transcription, "apple transcription");
+
+        let fields = profile.anki_fields();
+        assert_eq!(fields.word, "Hanzi");
+        assert_eq!(fields.transcription, "Pinyin");
+    }
 }
@@ -0,0 +1,205 @@
+//! Export of words as table (CSV, TSV)
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::Result;
+
+use crate::{
+    db::{Category, Example, Word},
+    export::Exporter,
+    profile::AppProfile,
+};
+
+#[derive(Debug, Clone)]
+pub struct TableOptions {
+    pub delimiter: char,
+    /// Write names of columns in first line
+    pub header: bool,
+    pub escape: Escape,
+}
+
+/// How special characters in values are escaped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escape {
+    /// Values with delimiter, quotes or new lines are quoted, as in RFC 4180
+    Quote,
+    /// Delimiter, new lines and backslashes are escaped with backslash
+    Backslash,
+}
+
+impl TableOptions {
+    pub fn csv() -> Self {
+        Self {
+            delimiter: ',',
+            header: true,
+            escape: Escape::Quote,
+        }
+    }
+    pub fn tsv() -> Self {
+        Self {
+            delimiter: '\t',
+            ..Self::csv()
+        }
+    }
+}
+
+impl FromStr for Escape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quote" => Ok(Self::Quote),
+            "backslash" => Ok(Self::Backslash),
+            _ => Err(format!("unknown escape: {s}, expected quote or backslash")),
+        }
+    }
+}
+
+pub struct TableWriter<'a> {
+    out: BufWriter<File>,
+    profile: &'a dyn AppProfile,
+    /// Category's id to name
+    categories: HashMap<String, String>,
+    options: TableOptions,
+}
+
+impl<'a> TableWriter<'a> {
+    pub fn new(
+        path: impl AsRef<Path>,
+        profile: &'a dyn AppProfile,
+        categories: &[Category],
+        options: TableOptions,
+    ) -> Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+            profile,
+            categories: categories
+                .iter()
+                .map(|c| (c.id.clone(), c.name.clone()))
+                .collect(),
+            options,
+        })
+    }
+    fn row(&mut self, values: &[String]) -> Result<()> {
+        let row: Vec<_> = values
+            .iter()
+            .map(|v| escape(v, self.options.delimiter, self.options.escape))
+            .collect();
+        writeln!(
+            self.out,
+            "{}",
+            row.join(&self.options.delimiter.to_string())
+        )?;
+        Ok(())
+    }
+}
+
+impl Exporter for TableWriter<'_> {
+    fn begin(&mut self) -> Result<()> {
+        if self.options.header {
+            let names = self.profile.anki_fields();
+            self.row(&[
+                names.word,
+                names.reading,
+                names.translate,
+                names.transcription,
+                "Examples".to_string(),
+                "Categories".to_string(),
+            ])?;
+        }
+        Ok(())
+    }
+    fn word(&mut self, w: &Word) -> Result<()> {
+        let fields = self.profile.anki_values(w);
+        let categories: Vec<_> = w
+            .category_ids
+            .iter()
+            .map(|c| self.categories.get(c).unwrap_or(c).as_str())
+            .collect();
+        self.row(&[
+            fields.word.unwrap_or_default(),
+            fields.reading.unwrap_or_default(),
+            fields.translate.unwrap_or_default(),
+            fields.transcription,
+            examples_to_text(fields.examples.as_deref()),
+            categories.join(", "),
+        ])
+    }
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// One example per line, "original - translate"
+fn examples_to_text(examples: Option<&[Example]>) -> String {
+    examples
+        .unwrap_or_default()
+        .iter()
+        .map(|e| {
+            format!(
+                "{} - {}",
+                e.original.replace('#', ""),
+                e.translate.replace('#', "")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn escape(value: &str, delimiter: char, escape: Escape) -> String {
+    match escape {
+        Escape::Quote => {
+            if value.contains([delimiter, '"', '\n', '\r']) {
+                format!("\"{}\"", value.replace('"', "\"\""))
+            } else {
+                value.to_string()
+            }
+        }
+        Escape::Backslash => {
+            let mut res = String::with_capacity(value.len());
+            for c in value.chars() {
+                match c {
+                    '\\' => res.push_str("\\\\"),
+                    '\n' => res.push_str("\\n"),
+                    '\r' => res.push_str("\\r"),
+                    '\t' => res.push_str("\\t"),
+                    c if c == delimiter => {
+                        res.push('\\');
+                        res.push(c);
+                    }
+                    c => res.push(c),
+                }
+            }
+            res
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        let table = [
+            ("asdf", ',', Escape::Quote, "asdf"),
+            ("as,df", ',', Escape::Quote, "\"as,df\""),
+            ("as\"df", '\t', Escape::Quote, "\"as\"\"df\""),
+            ("as\ndf", '\t', Escape::Quote, "\"as\ndf\""),
+            ("as,df", '\t', Escape::Quote, "as,df"),
+            ("as\tdf", '\t', Escape::Backslash, "as\\tdf"),
+            ("as\\df\n", ';', Escape::Backslash, "as\\\\df\\n"),
+            ("as;df", ';', Escape::Backslash, "as\\;df"),
+        ];
+        for (value, delimiter, esc, expected) in table {
+            assert_eq!(escape(value, delimiter, esc), expected);
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! Updating notes, which are already imported to Anki
+//!
+//! Notes are matched by guid (`reword-{kind}-{id}`). To not overwrite user's
+//! edits, values of fields from previous export are saved to [`Baseline`]:
+//! field is updated only when it was changed in app and was not edited by user
+
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+use crate::temp_path;
+
+/// Collections inside `.apkg`, in order of preference
+const APKG_COLLECTIONS: [&str; 2] = ["collection.anki21", "collection.anki2"];
+
+/// Collection of new Anki versions, it is compressed and is not supported. When
+/// it is present, `collection.anki2` is only a placeholder without notes
+const APKG_COLLECTION_NEW: &str = "collection.anki21b";
+
+/// Separator of fields in `notes.flds`
+const FIELDS_SEPARATOR: char = '\x1f';
+
+/// Notes of existing collection, by guid
+#[derive(Debug, Default)]
+pub struct Collection {
+    notes: HashMap<String, ExistingNote>,
+}
+
+#[derive(Debug)]
+struct ExistingNote {
+    model_id: i64,
+    fields: Vec<String>,
+    tags: Vec<String>,
+}
+
+impl Collection {
+    /// Read notes from `.apkg` or `collection.anki2`. Collection should contain
+    /// notes exported by this application
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+        let collection = if data.starts_with(b"PK") {
+            Self::from_apkg(data)?
+        } else {
+            Self::from_db(path)?
+        };
+        if collection.is_empty() {
+            return Err(anyhow!("no notes from Reword found in collection"));
+        }
+        Ok(collection)
+    }
+    fn from_apkg(data: Vec<u8>) -> Result<Self> {
+        let mut zip = ZipArchive::new(Cursor::new(data))?;
+        let has_file = |name: &str| zip.file_names().any(|f| f == name);
+        let name = if has_file(APKG_COLLECTION_NEW) && !has_file(APKG_COLLECTIONS[0]) {
+            None
+        } else {
+            APKG_COLLECTIONS.into_iter().find(|n| has_file(n))
+        };
+        let name = name.ok_or_else(|| {
+            anyhow!(
+                "collection not found in package, export it with \"Support older Anki versions\""
+            )
+        })?;
+        let mut buf = vec![];
+        zip.by_name(name)?.read_to_end(&mut buf)?;
+
+        let db_path = temp_path("collection.db");
+        let collection = std::fs::write(&db_path, buf)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| Self::from_db(&db_path));
+        let _ = std::fs::remove_file(&db_path);
+        collection
+    }
+    fn from_db(path: &Path) -> Result<Self> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut stmt =
+            conn.prepare("select guid, mid, flds, tags from notes where guid like 'reword-%'")?;
+        let notes = stmt
+            .query_map([], |r| {
+                let fields: String = r.get("flds")?;
+                let tags: String = r.get("tags")?;
+                Ok((
+                    r.get("guid")?,
+                    ExistingNote {
+                        model_id: r.get("mid")?,
+                        fields: fields.split(FIELDS_SEPARATOR).map(String::from).collect(),
+                        tags: tags.split_whitespace().map(String::from).collect(),
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(Self { notes })
+    }
+    /// Decide, how note should be exported. `fields` are values from app
+    pub fn update(
+        &self,
+        guid: &str,
+        model_id: i64,
+        fields: &[String],
+        baseline: Option<&[String]>,
+    ) -> Update {
+        let Some(note) = self.notes.get(guid) else {
+            return Update::New;
+        };
+        if note.model_id != model_id {
+            return Update::OtherModel;
+        }
+        let (merged, conflict) = merge(fields, baseline, &note.fields);
+        if merged == note.fields {
+            Update::Unchanged { conflict }
+        } else {
+            Update::Changed {
+                fields: merged,
+                tags: note.tags.clone(),
+                conflict,
+            }
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Update {
+    /// Note is not in collection
+    New,
+    /// Some fields should be updated. `fields` are all fields of note, `tags`
+    /// are tags of note in collection, they are kept
+    Changed {
+        fields: Vec<String>,
+        tags: Vec<String>,
+        conflict: bool,
+    },
+    Unchanged {
+        conflict: bool,
+    },
+    /// Note in collection has different note type, it is not updated
+    OtherModel,
+}
+
+/// Updating of collection during export
+#[derive(Debug)]
+pub struct Updater {
+    /// Without collection all notes are new, only baseline is recorded
+    collection: Option<Collection>,
+    baseline: Baseline,
+    stats: UpdateStats,
+}
+
+/// Result of updating collection
+#[derive(Debug, Default)]
+pub struct UpdateStats {
+    pub new: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    pub other_model: usize,
+    /// Notes, where app's changes are not applied because of user's edits
+    pub conflicts: usize,
+}
+
+impl Updater {
+    pub fn new(collection: Option<Collection>, baseline: Baseline) -> Self {
+        Self {
+            collection,
+            baseline,
+            stats: UpdateStats::default(),
+        }
+    }
+    /// Decide, how note should be exported, see [`Collection::update`]
+    pub fn check(&self, guid: &str, model_id: i64, fields: &[String]) -> Update {
+        match &self.collection {
+            Some(c) => c.update(guid, model_id, fields, self.baseline.get(guid)),
+            None => Update::New,
+        }
+    }
+    /// Save exported values from app to baseline and count result of `update`
+    pub fn record(&mut self, guid: &str, fields: &[String], update: &Update) {
+        self.baseline.insert(guid.to_string(), fields.to_vec());
+
+        let conflict = match update {
+            Update::New => {
+                self.stats.new += 1;
+                false
+            }
+            Update::Changed { conflict, .. } => {
+                self.stats.changed += 1;
+                *conflict
+            }
+            Update::Unchanged { conflict } => {
+                self.stats.unchanged += 1;
+                *conflict
+            }
+            Update::OtherModel => {
+                self.stats.other_model += 1;
+                false
+            }
+        };
+        if conflict {
+            self.stats.conflicts += 1;
+        }
+    }
+    /// Values from app, which should be saved after export
+    pub fn baseline(&self) -> &Baseline {
+        &self.baseline
+    }
+    pub fn stats(&self) -> &UpdateStats {
+        &self.stats
+    }
+}
+
+/// Values of fields from app, which were exported last time, by guid
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    notes: HashMap<String, Vec<String>>,
+}
+
+impl Baseline {
+    /// Read baseline, if file does not exist, baseline is empty
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        match std::fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+    pub fn get(&self, guid: &str) -> Option<&[String]> {
+        self.notes.get(guid).map(|f| f.as_slice())
+    }
+    pub fn insert(&mut self, guid: String, fields: Vec<String>) {
+        self.notes.insert(guid, fields);
+    }
+}
+
+/// Merge values from app with values in collection. Field is taken from app,
+/// only when it was changed in app since `baseline` and user did not edit it.
+/// Without baseline, different values are considered as user's edits. Returns
+/// merged fields and whether app's changes conflicted with user's edits
+fn merge(new: &[String], baseline: Option<&[String]>, current: &[String]) -> (Vec<String>, bool) {
+    let mut conflict = false;
+    let merged = new
+        .iter()
+        .enumerate()
+        .map(|(i, new)| {
+            let Some(current) = current.get(i) else {
+                return new.clone();
+            };
+            match baseline.and_then(|b| b.get(i)) {
+                _ if current == new => current.clone(),
+                Some(old) if old == new => current.clone(),
+                Some(old) if old == current => new.clone(),
+                _ => {
+                    conflict = true;
+                    current.clone()
+                }
+            }
+        })
+        .collect();
+    (merged, conflict)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_dir::TestDir;
+
+    use super::*;
+
+    fn fields(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_merge() {
+        let baseline = fields(&["apple", "яблоко", "", "old"]);
+        // user edited 2nd field, app changed 3rd and 4th
+        let current = fields(&["apple", "яблочко", "", "edited"]);
+        let new = fields(&["apple", "яблоко", "ˈæp.əl", "new"]);
+
+        let (merged, conflict) = merge(&new, Some(&baseline), &current);
+        assert_eq!(merged, fields(&["apple", "яблочко", "ˈæp.əl", "edited"]));
+        assert!(conflict);
+
+        let (merged, conflict) = merge(&new, None, &current);
+        assert_eq!(merged, current);
+        assert!(conflict);
+
+        let (merged, conflict) = merge(&new, Some(&baseline), &baseline);
+        assert_eq!(merged, new);
+        assert!(!conflict);
+    }
+
+    #[test]
+    fn test_collection_update() {
+        let dir = TestDir::new();
+        let path = dir.join("collection.anki2");
+
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "create table notes (id integer, guid text, mid integer, flds text, tags text);
+             insert into notes values
+               (1, 'reword-eng-1', 10, 'apple' || char(31) || 'яблоко', ' food edited '),
+               (2, 'reword-eng-2', 20, 'ticket' || char(31) || 'билет', ''),
+               (3, 'other', 10, '', '');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let collection = Collection::open(&path).unwrap();
+        assert_eq!(collection.len(), 2);
+
+        let apple = fields(&["apple", "яблоко"]);
+        assert_eq!(
+            collection.update("reword-eng-1", 10, &apple, None),
+            Update::Unchanged { conflict: false }
+        );
+        assert_eq!(
+            collection.update(
+                "reword-eng-1",
+                10,
+                &fields(&["apple", "яблоки"]),
+                Some(&apple)
+            ),
+            Update::Changed {
+                fields: fields(&["apple", "яблоки"]),
+                tags: fields(&["food", "edited"]),
+                conflict: false
+            }
+        );
+        assert_eq!(
+            collection.update("reword-eng-2", 10, &apple, None),
+            Update::OtherModel
+        );
+        assert_eq!(
+            collection.update("reword-eng-3", 10, &apple, None),
+            Update::New
+        );
+    }
+
+    #[test]
+    fn test_open_collection() {
+        let dir = TestDir::new();
+        let path = dir.join("collection.anki2");
+
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "create table notes (id integer, guid text, mid integer, flds text, tags text);
+             insert into notes values (1, 'other', 10, '', '');",
+        )
+        .unwrap();
+        drop(conn);
+        assert!(Collection::open(&path).is_err(), "no notes from Reword");
+
+        // package of new Anki versions, with placeholder collection.anki2
+        let placeholder = std::fs::read(&path).unwrap();
+        let apkg = dir.join("collection.apkg");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&apkg).unwrap());
+        for (name, data) in [
+            ("collection.anki2", placeholder.as_slice()),
+            ("collection.anki21b", b"compressed"),
+        ] {
+            zip.start_file(name, Default::default()).unwrap();
+            std::io::Write::write_all(&mut zip, data).unwrap();
+        }
+        zip.finish().unwrap();
+        let err = Collection::open(&apkg).unwrap_err();
+        assert!(err.to_string().contains("Support older Anki versions"));
+    }
+}